@@ -0,0 +1,94 @@
+use std::collections::VecDeque;
+
+use discrete_multi_nav::{grid::map::GridMap, pathfind::common::MultipleEnds, simulator::Simulator};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+// every instance is generated from a fixed seed so that runs are comparable
+pub const SEED: u64 = 20240401;
+
+pub struct Instance {
+    pub name: String,
+    pub map: GridMap,
+    // (start, goal)
+    pub agents: Vec<((usize, usize), (usize, usize))>,
+}
+
+impl Instance {
+    #[allow(dead_code)]
+    pub fn simulator(&self, max_reservation_time: u32) -> Simulator<GridMap, u32> {
+        let mut s = Simulator::new(0, self.map.clone(), max_reservation_time);
+        for &(start, goal) in &self.agents {
+            s.add((), start, VecDeque::from([MultipleEnds::new_as_all_zero(vec![goal])])).unwrap();
+        }
+        s
+    }
+
+    fn new(name: String, map: GridMap, n_agents: usize, rng: &mut StdRng) -> Self {
+        let mut cells = (0..map.nx())
+            .flat_map(|x| (0..map.ny()).map(move |y| (x, y)))
+            .filter(|&p| !map.is_blocked(p))
+            .collect::<Vec<_>>();
+        cells.shuffle(rng);
+        let starts = cells[..n_agents].to_vec();
+        cells.shuffle(rng);
+        let goals = &cells[..n_agents];
+        Self { name, agents: starts.into_iter().zip(goals.iter().copied()).collect(), map }
+    }
+}
+
+// uniformly random obstacles
+pub fn random_grid(size: usize, density: f64, n_agents: usize) -> Instance {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let mut map = GridMap::new(size, size);
+    for x in 0..size {
+        for y in 0..size {
+            map.set_blocked((x, y), rng.gen_bool(density));
+        }
+    }
+    Instance::new(format!("random-{}-{}", size, n_agents), map, n_agents, &mut rng)
+}
+
+// rooms separated by walls with one door to each neighbour, like room-*-*-* of the MovingAI benchmark
+pub fn rooms(n_rooms: usize, room_size: usize, n_agents: usize) -> Instance {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let size = n_rooms * (room_size + 1) - 1;
+    let mut map = GridMap::new(size, size);
+    for k in 1..n_rooms {
+        let w = k * (room_size + 1) - 1;
+        for i in 0..size {
+            map.set_blocked((w, i), true);
+            map.set_blocked((i, w), true);
+        }
+    }
+    for k in 1..n_rooms {
+        let w = k * (room_size + 1) - 1;
+        for r in 0..n_rooms {
+            let (d0, d1) = (rng.gen_range(0..room_size), rng.gen_range(0..room_size));
+            map.set_blocked((w, r * (room_size + 1) + d0), false);
+            map.set_blocked((r * (room_size + 1) + d1, w), false);
+        }
+    }
+    Instance::new(format!("rooms-{}-{}-{}", n_rooms, room_size, n_agents), map, n_agents, &mut rng)
+}
+
+// agents swap the ends of a corridor of width 2
+pub fn corridor(length: usize, n_agents: usize) -> Instance {
+    let map = GridMap::new(length, 2);
+    let agents = (0..n_agents.min(length))
+        .map(|i| {
+            let (left, right) = ((i / 2, i % 4 / 2), (length - 1 - i / 2, i % 4 / 2));
+            if i % 2 == 0 { (left, right) } else { (right, left) }
+        })
+        .collect();
+    Instance { name: format!("corridor-{}-{}", length, n_agents), map, agents }
+}
+
+pub fn instances() -> Vec<Instance> {
+    vec![
+        random_grid(32, 0.2, 20),
+        random_grid(64, 0.2, 50),
+        rooms(4, 7, 20),
+        rooms(8, 7, 50),
+        corridor(20, 8),
+    ]
+}
@@ -0,0 +1,39 @@
+use std::iter::once;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use discrete_multi_nav::{map::{Heuristic, Map}, pathfind::{astar::astar_for_next_reservation, common::MultipleEnds, dijkstra::dijkstra_for_next_reservation}};
+
+mod common;
+
+// plans for the first agent while the cells of the others are reserved
+fn bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("next_reservation");
+
+    for instance in common::instances() {
+        let map = &instance.map;
+        let ((start, goal), others) = instance.agents.split_first().unwrap();
+        let successors = |n: &(usize, usize)| map
+            .successors(n, &())
+            .map(|(i, m, c)| (m, c, once(m), i))
+            .collect::<Vec<_>>();
+        let seats_reservation = |s: &(usize, usize)| !others.iter().any(|(o, _)| o == s);
+        let ends = MultipleEnds::new_as_all_zero(vec![*goal]);
+        let heuristic = map.heuristic(&ends).unwrap();
+
+        // 5 is the usual horizon of the simulator, the longer one plans the whole way
+        for max_reservation_time in [5, 4 * (map.nx() + map.ny()) as u32] {
+            let id = format!("{}/{}", instance.name, max_reservation_time);
+
+            group.bench_with_input(BenchmarkId::new("dijkstra", &id), start, |b, start| {
+                b.iter(|| dijkstra_for_next_reservation(*start, &ends, successors, seats_reservation, max_reservation_time))
+            });
+            group.bench_with_input(BenchmarkId::new("astar", &id), start, |b, start| {
+                b.iter(|| astar_for_next_reservation(*start, &ends, successors, seats_reservation, max_reservation_time, |n| heuristic.heuristic(n)))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
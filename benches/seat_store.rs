@@ -0,0 +1,53 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use discrete_multi_nav::{index::index::Idx, seat::Seat, store::DenseSeatStore};
+
+// occupancies of a grid as `Nullable` in `DenseSeatStore` and as `Option<Idx>`, filled every third seat
+fn bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("seat_store");
+
+    for n in [64, 512] {
+        let seats = (0..n).flat_map(|x| (0..n).map(move |y| (x, y))).filter(|(x, y)| (x + y) % 3 == 0).collect::<Vec<_>>();
+
+        group.bench_with_input(BenchmarkId::new("dense_add_remove", n), &seats, |b, seats| {
+            let mut store = DenseSeatStore::<(usize, usize), (), u32>::new((n, n));
+            b.iter(|| {
+                for (k, &p) in seats.iter().enumerate() {
+                    store[p].add(Idx::new(k as u32)).unwrap();
+                }
+                for (k, &p) in seats.iter().enumerate() {
+                    store[p].remove(Idx::new(k as u32)).unwrap();
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("option_add_remove", n), &seats, |b, seats| {
+            let mut store = vec![vec![None::<Idx<(), u32>>; n]; n];
+            b.iter(|| {
+                for (k, &(x, y)) in seats.iter().enumerate() {
+                    assert!(store[x][y].is_none());
+                    store[x][y] = Some(Idx::new(k as u32));
+                }
+                for (k, &(x, y)) in seats.iter().enumerate() {
+                    assert_eq!(store[x][y], Some(Idx::new(k as u32)));
+                    store[x][y] = None;
+                }
+            })
+        });
+
+        let mut dense = DenseSeatStore::<(usize, usize), (), u32>::new((n, n));
+        let mut option = vec![vec![None::<Idx<(), u32>>; n]; n];
+        for (k, &(x, y)) in seats.iter().enumerate() {
+            dense[(x, y)].add(Idx::new(k as u32)).unwrap();
+            option[x][y] = Some(Idx::new(k as u32));
+        }
+        group.bench_with_input(BenchmarkId::new("dense_occupied", n), &dense, |b, store| {
+            b.iter(|| black_box(store.occupied().count()))
+        });
+        group.bench_with_input(BenchmarkId::new("option_occupied", n), &option, |b, store| {
+            b.iter(|| black_box(store.iter().enumerate().flat_map(|(x, c)| c.iter().enumerate().filter_map(move |(y, i)| i.map(|i| ((x, y), i)))).count()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -0,0 +1,23 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+mod common;
+
+// one step after warming up, when most agents are moving and replanning
+fn bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step");
+
+    for instance in common::instances() {
+        for n_warmup in [0, 20] {
+            let mut s = instance.simulator(5);
+            s.run_for(n_warmup, |_| {}).unwrap();
+
+            group.bench_with_input(BenchmarkId::new(&instance.name, n_warmup), &s, |b, s| {
+                b.iter_batched(|| s.clone(), |mut s| { s.step().unwrap(); s }, BatchSize::SmallInput)
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -1,73 +1,167 @@
 use std::collections::VecDeque;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize};
+
 use crate::pathfind::common::{Cost, MultipleEnds, Node};
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct AgentData<N: Node, C: Cost, T = ()>
 {
     kind: T,
     current: N,
     state: AgentState<N, C>,
     destinations: VecDeque<MultipleEnds<N, C>>,
-    removing: bool,
+    // set while the agent is being removed
+    #[cfg_attr(feature = "serde", serde(alias = "removing", default, deserialize_with = "deserialize_removal"))]
+    removal: Option<RemovalMode>,
+}
+
+// snapshots before the removal modes have `removing: bool`, whose removals are graceful
+#[cfg(feature = "serde")]
+fn deserialize_removal<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<RemovalMode>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Removal {
+        Removing(bool),
+        Mode(Option<RemovalMode>),
+    }
+
+    Ok(match Removal::deserialize(deserializer)? {
+        Removal::Removing(removing) => removing.then_some(RemovalMode::Graceful),
+        Removal::Mode(mode) => mode,
+    })
 }
 
 impl<T: Default, N: Node, C: Cost> AgentData<N, C, T> {
     pub fn new_default(current: N, destinations: VecDeque<MultipleEnds<N, C>>) -> Self {
-        Self { kind: T::default(), current, state: AgentState::NotPlaced, destinations, removing: false }
+        Self { kind: T::default(), current, state: AgentState::NotPlaced, destinations, removal: None }
     }
 }
 
 impl<T, N: Node, C: Cost> AgentData<N, C, T> {
     pub fn new(kind: T, current: N, destinations: VecDeque<MultipleEnds<N, C>>) -> Self {
-        Self { kind, current, state: AgentState::NotPlaced, destinations, removing: false }
+        Self { kind, current, state: AgentState::NotPlaced, destinations, removal: None }
     }
 
     pub fn kind(&self) -> &T { &self.kind }
     pub fn current(&self) -> &N { &self.current }
     pub fn state(&self) -> &AgentState<N, C> { &self.state }
-    pub fn next_destinations(&self) -> Option<&MultipleEnds<N, C>> { self.destinations.get(0) }
+    pub fn next_destinations(&self) -> Option<&MultipleEnds<N, C>> { self.destinations.front() }
     pub fn all_destinations(&self) -> &VecDeque<MultipleEnds<N, C>> { &self.destinations }
     pub fn destinations_mut(&mut self) -> &mut VecDeque<MultipleEnds<N, C>> { &mut self.destinations }
-    pub fn removing(&self) -> bool { self.removing }
+    pub fn removing(&self) -> bool { self.removal.is_some() }
+    pub fn removal(&self) -> Option<RemovalMode> { self.removal }
 
-    pub(crate) fn remove(&mut self) -> bool {
-        if self.removing {
+    // a pending removal can only be made more urgent
+    pub(crate) fn remove(&mut self, mode: RemovalMode) -> bool {
+        if self.removal.is_some_and(|m| m >= mode) {
             return false
         }
-        self.removing = true;
-        return true
+        self.removal = Some(mode);
+        true
+    }
+
+    pub(crate) fn cancel_removal(&mut self) -> bool {
+        self.removal.take().is_some()
     }
     
     pub(crate) fn place(&mut self) {
         self.state = AgentState::Stop;
     }
+    // a moving agent continues after its current path
     pub(crate) fn departs<I: Iterator<Item = (N, C)>>(&mut self, nexts: I) {
+        if let AgentState::Moving { nexts: n } = &mut self.state {
+            n.extend(nexts);
+            return
+        }
         let nexts = VecDeque::from_iter(nexts);
         if nexts.is_empty() {
             return
         }
         self.state = AgentState::Moving { nexts }
     }
-    pub(crate) fn arrives(&mut self) {
+    pub(crate) fn stops_at_next(&mut self) {
         if let AgentState::Moving { nexts } = &mut self.state {
-            if let Some((n, _)) = nexts.pop_front() {
-                self.current = n;
-            }
-            if nexts.is_empty() {
-                self.state = AgentState::Stop;
-
-                if self.destinations[0].end_index(&self.current).is_some() {
-                    self.destinations.pop_front();
-                }
-            }
+            nexts.truncate(1);
         }
     }
+    // returns whether one of the next destinations is reached if the agent stops
+    pub(crate) fn arrives(&mut self) -> Option<bool> {
+        let AgentState::Moving { nexts } = &mut self.state else { return None };
+        if let Some((n, _)) = nexts.pop_front() {
+            self.current = n;
+        }
+        if !nexts.is_empty() {
+            return None
+        }
+        self.state = AgentState::Stop;
+
+        let reached = self.destinations.front().is_some_and(|d| d.end_index(&self.current).is_some());
+        if reached {
+            self.destinations.pop_front();
+        }
+        Some(reached)
+    }
     
 }
 
-#[derive(Debug, PartialEq, Eq)]
+// when an agent being removed leaves the simulator, in the order of urgency
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RemovalMode {
+    // at the end of its reserved path
+    #[default]
+    Graceful,
+    // at the node it is moving to, the rest of its path is cancelled
+    AtNextNode,
+    // at the beginning of the next step wherever it is, all its seats are released
+    Immediate,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum AgentState<N, C> {
     NotPlaced,
     Stop,
     Moving { nexts: VecDeque<(N, C)> }
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_test() {
+        use std::collections::VecDeque;
+
+        use crate::pathfind::common::MultipleEnds;
+
+        use super::{AgentData, AgentState, RemovalMode};
+
+        let mut a = AgentData::new(3u8, (0, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(2, 0)])]));
+        a.place();
+        a.departs([((1, 0), 1), ((2, 0), 2)].into_iter());
+
+        let json = serde_json::to_string(&a).unwrap();
+        let b = serde_json::from_str::<AgentData<(i32, i32), u32, u8>>(&json).unwrap();
+
+        assert_eq!(a.kind(), b.kind());
+        assert_eq!(a.current(), b.current());
+        assert_eq!(b.state(), &AgentState::Moving { nexts: VecDeque::from([((1, 0), 1), ((2, 0), 2)]) });
+        assert_eq!(b.all_destinations().len(), 1);
+        assert!(!b.removing());
+
+        a.remove(RemovalMode::AtNextNode);
+        let json = serde_json::to_string(&a).unwrap();
+        let b = serde_json::from_str::<AgentData<(i32, i32), u32, u8>>(&json).unwrap();
+        assert_eq!(b.removal(), Some(RemovalMode::AtNextNode));
+
+        // snapshots before the removal modes
+        let old = |removing: &str| json.replace(r#""removal":"AtNextNode""#, &format!(r#""removing":{}"#, removing));
+        let b = serde_json::from_str::<AgentData<(i32, i32), u32, u8>>(&old("true")).unwrap();
+        assert_eq!(b.removal(), Some(RemovalMode::Graceful));
+        let b = serde_json::from_str::<AgentData<(i32, i32), u32, u8>>(&old("false")).unwrap();
+        assert_eq!(b.removal(), None);
+    }
+}
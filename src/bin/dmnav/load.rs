@@ -0,0 +1,157 @@
+use std::{collections::{BTreeMap, VecDeque}, fs, path::Path};
+
+use discrete_multi_nav::{graph::GraphMap, grid::{ascii::GridScenario, map::GridMap}, pathfind::common::MultipleEnds, scenario::{MapFormat, MapRef}};
+use serde::Deserialize;
+
+pub(crate) struct GraphScenario {
+    pub(crate) map: GraphMap,
+    pub(crate) agents: Vec<(usize, VecDeque<MultipleEnds<usize, u32>>)>,
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub(crate) fn ascii_grid(path: &Path) -> Result<GridScenario, String> {
+    read(path)?.parse().map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// MovingAI benchmark format, y of the files points downward
+pub(crate) fn moving_ai(map_path: &Path, scenario_path: &Path, n_agents: Option<usize>) -> Result<GridScenario, String> {
+    let map = moving_ai_map(map_path)?;
+    let ny = map.ny();
+
+    let text = read(scenario_path)?;
+    let mut agents = BTreeMap::new();
+    for (l, line) in text.lines().enumerate().filter(|(_, line)| !line.starts_with("version") && !line.trim().is_empty()) {
+        if n_agents.is_some_and(|n| agents.len() >= n) {
+            break;
+        }
+        let error = || format!("{}: invalid line {}", scenario_path.display(), l + 1);
+
+        // bucket, map, width, height, start x, start y, goal x, goal y, optimal length
+        let columns = line.split_whitespace().collect::<Vec<_>>();
+        let [x0, y0, x1, y1] = [4, 5, 6, 7].map(|i| columns.get(i).and_then(|c| c.parse::<usize>().ok()));
+        let (Some(x0), Some(y0), Some(x1), Some(y1)) = (x0, y0, x1, y1) else { return Err(error()) };
+        let (start, goal) = ((x0, ny.wrapping_sub(1 + y0)), (x1, ny.wrapping_sub(1 + y1)));
+        if !map.contains(start) || !map.contains(goal) || map.is_blocked(start) || map.is_blocked(goal) {
+            return Err(error())
+        }
+        agents.insert(agents.len() as u32, (start, vec![goal]));
+    }
+
+    Ok(GridScenario { map, agents })
+}
+
+pub(crate) fn moving_ai_map(map_path: &Path) -> Result<GridMap, String> {
+    let text = read(map_path)?;
+    let error = |m: &str| format!("{}: {}", map_path.display(), m);
+
+    let mut lines = text.lines();
+    let (mut nx, mut ny) = (None, None);
+    for line in lines.by_ref() {
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["map"] => break,
+            ["height", h] => ny = h.parse::<usize>().ok(),
+            ["width", w] => nx = w.parse::<usize>().ok(),
+            _ => {},
+        }
+    }
+    let (Some(nx), Some(ny)) = (nx, ny) else { return Err(error("width or height is missing")) };
+
+    let mut map = GridMap::new(nx, ny);
+    let rows = lines.collect::<Vec<_>>();
+    if rows.len() < ny {
+        return Err(error("the map has fewer rows than its height"))
+    }
+    for (row, line) in rows.iter().take(ny).enumerate() {
+        for (x, c) in line.chars().take(nx).enumerate() {
+            if !matches!(c, '.' | 'G' | 'S') {
+                map.set_blocked((x, ny - 1 - row), true);
+            }
+        }
+    }
+
+    Ok(map)
+}
+
+#[derive(Deserialize)]
+struct JsonGraph {
+    nodes: Vec<JsonNode>,
+    // (from, to, cost)
+    edges: Vec<(usize, usize, u32)>,
+    #[serde(default)]
+    agents: Vec<JsonAgent>,
+}
+
+#[derive(Deserialize)]
+struct JsonNode {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Deserialize)]
+struct JsonScenario {
+    agents: Vec<JsonAgent>,
+}
+
+#[derive(Deserialize)]
+struct JsonAgent {
+    start: usize,
+    // each element is a set of nodes any of which can be reached
+    destinations: Vec<Vec<usize>>,
+}
+
+pub(crate) fn json_graph(map_path: &Path, scenario_path: Option<&Path>, n_agents: Option<usize>) -> Result<GraphScenario, String> {
+    let (map, agents) = json_graph_map(map_path)?;
+
+    let (path, agents) = if let Some(path) = scenario_path {
+        let scenario: JsonScenario = serde_json::from_str(&read(path)?).map_err(|e| format!("{}: {}", path.display(), e))?;
+        (path, scenario.agents)
+    } else {
+        (map_path, agents)
+    };
+
+    let agents = agents.into_iter()
+        .take(n_agents.unwrap_or(usize::MAX))
+        .enumerate()
+        .map(|(k, a)| {
+            if a.start >= map.len() || a.destinations.iter().flatten().any(|&n| n >= map.len()) {
+                return Err(format!("{}: agent {} refers to a missing node", path.display(), k))
+            }
+            Ok((a.start, a.destinations.into_iter().map(MultipleEnds::new_as_all_zero).collect()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(GraphScenario { map, agents })
+}
+
+fn json_graph_map(map_path: &Path) -> Result<(GraphMap, Vec<JsonAgent>), String> {
+    let graph: JsonGraph = serde_json::from_str(&read(map_path)?).map_err(|e| format!("{}: {}", map_path.display(), e))?;
+
+    let mut map = GraphMap::new();
+    for n in &graph.nodes {
+        map.add_node(n.x, n.y);
+    }
+    for (k, &(i, j, c)) in graph.edges.iter().enumerate() {
+        if !map.add_edge(i, j, c) {
+            return Err(format!("{}: edge {} refers to a missing node", map_path.display(), k))
+        }
+    }
+    Ok((map, graph.agents))
+}
+
+// agents in the map files are ignored, the scenario describes them
+pub(crate) fn map_of(map: &MapRef, scenario_path: &Path) -> Result<ScenarioMap, String> {
+    let path = scenario_path.parent().unwrap_or(Path::new("")).join(&map.path);
+    match map.format {
+        MapFormat::Ascii => ascii_grid(&path).map(|s| ScenarioMap::Grid(s.map)),
+        MapFormat::MovingAi => moving_ai_map(&path).map(ScenarioMap::Grid),
+        MapFormat::Graph => json_graph_map(&path).map(|(m, _)| ScenarioMap::Graph(m)),
+    }
+}
+
+pub(crate) enum ScenarioMap {
+    Grid(GridMap),
+    Graph(GraphMap),
+}
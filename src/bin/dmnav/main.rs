@@ -0,0 +1,221 @@
+#![allow(clippy::type_complexity)]
+
+use std::{env, fmt::Debug, fs::File, hash::Hash, io::BufWriter, path::{Path, PathBuf}, process::ExitCode, time::Instant};
+
+use discrete_multi_nav::{agent_data::AgentState, map::Map, metrics::Metrics, scenario::{self, Scenario, Schedule}, simulator::{Planner, Simulator, StopReason}, trajectory::TrajectoryRecorder};
+use load::ScenarioMap;
+
+mod load;
+
+const USAGE: &str = "\
+usage: dmnav <map> [options]
+
+maps:
+  *.toml  scenario description which refers to one of the maps below, its agents and settings are used as they are
+  *.map   MovingAI grid map, agents are read from --scenario (*.scen)
+  *.json  graph {\"nodes\": [{\"x\", \"y\"}], \"edges\": [[from, to, cost]], \"agents\": [{\"start\", \"destinations\": [[node]]}]},
+          agents can be given in a separate --scenario file {\"agents\": [...]}
+  *       ASCII grid diagram with agents and destinations (n: start of agent n, *n: destination of agent n, #: blocked)
+
+options:
+  --scenario <file>               agents for MovingAI and graph maps
+  --agents <n>                    use only the first n agents of the scenario, not for *.toml
+  --planner <astar|dijkstra>      default: astar
+  --max-reservation-time <t>      default: 5
+  --max-steps <n>                 default: 10000
+  --trajectory <file>             writes the trajectory for tests/visual/viewer/viewer.html
+  --metrics <file>                writes the metrics of each agent as CSV
+";
+
+struct Options {
+    map: PathBuf,
+    scenario: Option<PathBuf>,
+    n_agents: Option<usize>,
+    planner: Option<Planner>,
+    max_reservation_time: Option<u32>,
+    max_steps: usize,
+    trajectory: Option<PathBuf>,
+    metrics: Option<PathBuf>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut map = None;
+    let mut options = Options {
+        map: PathBuf::new(),
+        scenario: None,
+        n_agents: None,
+        planner: None,
+        max_reservation_time: None,
+        max_steps: 10000,
+        trajectory: None,
+        metrics: None,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--scenario" => options.scenario = Some(value()?.into()),
+            "--agents" => options.n_agents = Some(number(&arg, value()?)?),
+            "--planner" => options.planner = match value()?.as_str() {
+                "astar" => Some(Planner::AStar),
+                "dijkstra" => Some(Planner::Dijkstra),
+                p => return Err(format!("unknown planner: {}", p)),
+            },
+            "--max-reservation-time" => options.max_reservation_time = Some(number(&arg, value()?)?),
+            "--max-steps" => options.max_steps = number(&arg, value()?)?,
+            "--trajectory" => options.trajectory = Some(value()?.into()),
+            "--metrics" => options.metrics = Some(value()?.into()),
+            a if a.starts_with("--") => return Err(format!("unknown option: {}", a)),
+            _ if map.is_none() => map = Some(arg.into()),
+            a => return Err(format!("unexpected argument: {}", a)),
+        }
+    }
+
+    options.map = map.ok_or("the map is not given")?;
+    Ok(options)
+}
+
+fn number<N: std::str::FromStr>(arg: &str, value: String) -> Result<N, String> {
+    value.parse().map_err(|_| format!("{} needs a number, but got {}", arg, value))
+}
+
+fn main() -> ExitCode {
+    let result = parse_args(env::args().skip(1)).and_then(|options| run(&options));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            ExitCode::FAILURE
+        },
+    }
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let max_reservation_time = options.max_reservation_time.unwrap_or(5);
+    match options.map.extension().and_then(|e| e.to_str()) {
+        Some("toml") => {
+            if options.scenario.is_some() || options.n_agents.is_some() || options.planner.is_some() || options.max_reservation_time.is_some() {
+                return Err("scenario descriptions contain their agents and settings, --scenario, --agents, --planner and --max-reservation-time are not used".to_string())
+            }
+            let map = scenario::read_map_ref(&options.map).map_err(|e| e.to_string())?.ok_or("the scenario has no map")?;
+            match load::map_of(&map, &options.map)? {
+                ScenarioMap::Grid(map) => {
+                    let seats = grid_seats(map.nx(), map.ny(), |p| !map.is_blocked(p));
+                    let (s, schedule) = Scenario::load(&options.map).and_then(|sc| sc.build(map)).map_err(|e| e.to_string())?;
+                    simulate(s, Some(schedule), options, seats, |&(x, y)| (x as f32, y as f32))
+                },
+                ScenarioMap::Graph(map) => {
+                    let seats = map.nodes().iter().enumerate().map(|(i, n)| (i, n.nexts().clone())).collect();
+                    let coordinates = map.nodes().iter().map(|n| (n.x(), n.y())).collect::<Vec<_>>();
+                    let (s, schedule) = Scenario::load(&options.map).and_then(|sc| sc.build(map)).map_err(|e| e.to_string())?;
+                    simulate(s, Some(schedule), options, seats, |&i| coordinates[i])
+                },
+            }
+        },
+        Some("map") => {
+            let scenario = options.scenario.as_ref().ok_or("MovingAI maps need --scenario")?;
+            let scenario = load::moving_ai(&options.map, scenario, options.n_agents)?;
+            let seats = grid_seats(scenario.map.nx(), scenario.map.ny(), |p| !scenario.map.is_blocked(p));
+            let (s, _) = scenario.into_simulator(0, max_reservation_time).map_err(|e| e.to_string())?;
+            simulate(s, None, options, seats, |&(x, y)| (x as f32, y as f32))
+        },
+        Some("json") => {
+            let scenario = load::json_graph(&options.map, options.scenario.as_deref(), options.n_agents)?;
+            let seats = scenario.map.nodes().iter().enumerate().map(|(i, n)| (i, n.nexts().clone())).collect();
+            let coordinates = scenario.map.nodes().iter().map(|n| (n.x(), n.y())).collect::<Vec<_>>();
+            let mut s = Simulator::new(0, scenario.map, max_reservation_time);
+            for (k, (start, destinations)) in scenario.agents.into_iter().enumerate() {
+                s.add((), start, destinations).map_err(|e| format!("agent {}: {}", k, e))?;
+            }
+            simulate(s, None, options, seats, |&i| coordinates[i])
+        },
+        _ => {
+            if options.scenario.is_some() {
+                return Err("ASCII grids contain their agents, --scenario is not used".to_string())
+            }
+            let mut scenario = load::ascii_grid(&options.map)?;
+            if let Some(n) = options.n_agents {
+                scenario.agents = scenario.agents.into_iter().take(n).collect();
+            }
+            let seats = grid_seats(scenario.map.nx(), scenario.map.ny(), |p| !scenario.map.is_blocked(p));
+            let (s, _) = scenario.into_simulator(0, max_reservation_time).map_err(|e| e.to_string())?;
+            simulate(s, None, options, seats, |&(x, y)| (x as f32, y as f32))
+        },
+    }
+}
+
+fn grid_seats<F: Fn((usize, usize)) -> bool>(nx: usize, ny: usize, open: F) -> Vec<((usize, usize), Vec<(usize, u32)>)> {
+    (0..nx)
+        .flat_map(|x| (0..ny).map(move |y| (x, y)))
+        .filter(|&p| open(p))
+        .map(|p| (p, vec![]))
+        .collect()
+}
+
+fn simulate<M: Map<u32, Cost = u32>, F: FnMut(&M::SeatIndex) -> (f32, f32)>(
+    mut s: Simulator<M, u32>,
+    schedule: Option<Schedule<M::Node, u32, (), u32>>,
+    options: &Options,
+    seats: Vec<(M::SeatIndex, Vec<(usize, u32)>)>,
+    coordinate: F,
+) -> Result<(), String> where M::Node: Debug, M::SeatIndex: Hash + Debug {
+    if let Some(planner) = options.planner {
+        s.set_planner(planner);
+    }
+
+    let mut recorder = options.trajectory.as_ref().map(|_| TrajectoryRecorder::new_with_nexts(seats, coordinate));
+    if let Some(r) = recorder.as_mut() {
+        r.record(&s);
+    }
+
+    let mut metrics = Metrics::new(&s);
+
+    let start = Instant::now();
+    let f = |s: &Simulator<M, u32>| {
+        metrics.record(s);
+        if let Some(r) = recorder.as_mut() {
+            r.record(s);
+        }
+    };
+    let reason = match schedule {
+        Some(mut schedule) => schedule.run_with(&mut s, options.max_steps, f).map_err(|e| e.to_string()),
+        None => s.run_until_idle_with(options.max_steps, f).map_err(|e| e.to_string()),
+    }?;
+    let elapsed = start.elapsed();
+
+    let summary = metrics.summary();
+    let n_completed = s.agents().values().filter(|a| *a.state() == AgentState::Stop && a.all_destinations().is_empty()).count();
+    let result = match reason {
+        StopReason::Completed => "completed",
+        StopReason::Deadlock => "deadlock",
+        StopReason::StepLimit => "step limit",
+    };
+    println!("result: {}", result);
+    println!("agents: {}", s.agents().len());
+    println!("completed agents: {}", n_completed);
+    println!("steps: {}", summary.n_steps);
+    println!("time: {}", s.time());
+    println!("makespan: {}", summary.makespan);
+    println!("sum of costs: {}", summary.sum_of_costs);
+    println!("throughput: {:.3}", summary.throughput);
+    println!("average wait time: {:.3}", summary.average_wait_time);
+    println!("not placed time: {}", summary.not_placed_time);
+    println!("plans: {} ({} failed)", summary.n_plans, summary.n_failed_plans);
+    println!("planning time [ms]: {:.3}", summary.planning_time.as_secs_f64() * 1000.);
+    println!("cpu time [ms]: {:.3}", elapsed.as_secs_f64() * 1000.);
+
+    if let Some(path) = options.metrics.as_ref() {
+        let f = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        metrics.write_csv(BufWriter::new(f)).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+
+    if let (Some(path), Some(r)) = (options.trajectory.as_ref(), recorder) {
+        write_trajectory(path, r)?;
+    }
+    Ok(())
+}
+
+fn write_trajectory<M: Map<u32, Cost = u32>, F: FnMut(&M::SeatIndex) -> (f32, f32)>(path: &Path, recorder: TrajectoryRecorder<M, u32, F>) -> Result<(), String> {
+    let f = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    recorder.write(BufWriter::new(f)).map_err(|e| format!("{}: {}", path.display(), e))
+}
@@ -0,0 +1,149 @@
+use std::{cmp::Ordering, fmt::{Display, Formatter}, hash::{Hash, Hasher}, ops::{Add, Mul, Sub}};
+
+use num_traits::{One, Zero};
+#[cfg(feature = "serde")]
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+// f64 with a total order so that it satisfies `Cost` (NaN is rejected)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FloatCost(f64);
+
+impl FloatCost {
+    pub fn new(value: f64) -> Self {
+        assert!(!value.is_nan(), "FloatCost must not be NaN");
+        // normalize -0.0 to 0.0 so that equal values hash equally
+        Self(value + 0.0)
+    }
+
+    pub fn value(&self) -> f64 { self.0 }
+}
+
+impl From<f64> for FloatCost {
+    fn from(value: f64) -> Self { Self::new(value) }
+}
+
+impl From<FloatCost> for f64 {
+    fn from(value: FloatCost) -> Self { value.0 }
+}
+
+impl PartialEq for FloatCost {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for FloatCost {}
+
+impl PartialOrd for FloatCost {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FloatCost {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Hash for FloatCost {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+impl Add for FloatCost {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self::Output { Self::new(self.0 + rhs.0) }
+}
+
+impl Sub for FloatCost {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self::Output { Self::new(self.0 - rhs.0) }
+}
+
+impl Mul for FloatCost {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self::Output { Self::new(self.0 * rhs.0) }
+}
+
+impl Zero for FloatCost {
+    fn zero() -> Self { Self(0.0) }
+    fn is_zero(&self) -> bool { self.0 == 0.0 }
+}
+
+impl One for FloatCost {
+    fn one() -> Self { Self(1.0) }
+}
+
+impl Display for FloatCost {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for FloatCost {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for FloatCost {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = f64::deserialize(deserializer)?;
+        if value.is_nan() {
+            return Err(D::Error::custom("FloatCost must not be NaN"))
+        }
+        Ok(Self::new(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use crate::pathfind::{common::MultipleEnds, dijkstra::dijkstra_for_multiple_ends};
+
+    use super::FloatCost;
+
+    #[test]
+    fn order_test() {
+        let (a, b, c) = (FloatCost::new(0.5), FloatCost::new(1.25), FloatCost::new(-2.));
+
+        assert!(c < a && a < b);
+        assert_eq!(a + b, FloatCost::new(1.75));
+        assert_eq!(FloatCost::new(-0.), FloatCost::new(0.));
+        assert_eq!(HashSet::from([FloatCost::new(-0.), FloatCost::new(0.)]).len(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn nan_test() {
+        FloatCost::new(f64::NAN);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_test() {
+        let c = FloatCost::new(1.5);
+        assert_eq!(serde_json::to_string(&c).unwrap(), "1.5");
+        assert_eq!(serde_json::from_str::<FloatCost>("1.5").unwrap(), c);
+    }
+
+    #[test]
+    fn pathfind_test() {
+        // moving diagonally costs sqrt(2)
+        let successors = |&(x, y): &(i32, i32)| [(1, 0), (0, 1), (-1, 0), (0, -1), (1, 1), (-1, 1), (-1, -1), (1, -1)]
+            .into_iter()
+            .map(move |(dx, dy)| ((x + dx, y + dy), FloatCost::new(((dx * dx + dy * dy) as f64).sqrt()), ()));
+
+        let ends = MultipleEnds::new(HashMap::from([((3, 3), FloatCost::new(0.)), ((4, 0), FloatCost::new(0.5))]));
+        let path = dijkstra_for_multiple_ends(&(0, 0), &ends, successors, |c| c).unwrap();
+
+        assert_eq!(path.len(), 3);
+        assert_eq!(path[path.len() - 1].0, (3, 3));
+        assert!((path.total_cost().value() - 3. * 2f64.sqrt()).abs() < 1e-9);
+    }
+}
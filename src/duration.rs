@@ -1,8 +1,16 @@
 use std::cmp::Ordering;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use crate::{index::index::Idx, pathfind::common::{Cost, Seat}, seat::AgentIdxType};
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(
+    serialize = "C: Serialize, S: Serialize, U: Serialize",
+    deserialize = "C: Deserialize<'de>, S: Deserialize<'de>, U: Deserialize<'de>",
+)))]
 pub struct Duration<C: Cost, S: Seat, T, U: AgentIdxType> {
     time: C,
     index: Idx<T, U>,
@@ -19,6 +27,12 @@ impl<C: Cost, S: Seat, T, U: AgentIdxType> Duration<C, S, T, U> {
     pub fn seat(self) -> S { self.seat }
 }
 
+impl<C: Cost, S: Seat, T, U: AgentIdxType> Clone for Duration<C, S, T, U> {
+    fn clone(&self) -> Self {
+        Self { time: self.time, index: self.index, seat: self.seat.clone() }
+    }
+}
+
 impl<C: Cost, S: Seat, T, U: AgentIdxType> PartialEq for Duration<C, S, T, U> {
     fn eq(&self, other: &Self) -> bool {
         self.time == other.time
@@ -29,7 +43,7 @@ impl<C: Cost, S: Seat, T, U: AgentIdxType> Eq for Duration<C, S, T, U> {}
 
 impl<C: Cost, S: Seat, T, U: AgentIdxType> PartialOrd for Duration<C, S, T, U> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        other.time.partial_cmp(&self.time)
+        Some(self.cmp(other))
     }
 }
 
@@ -0,0 +1,122 @@
+use std::fmt::{Debug, Display, Formatter};
+
+use crate::{index::index::Idx, seat::{AgentIdxType, SeatError}, simulator::Violation};
+
+// errors of `Simulator`, `N` is the node and `S` the seat index of the map
+pub enum Error<N, S, T, U: AgentIdxType> {
+    UnknownAgent(Idx<T, U>),
+    // `remove` was called for an agent which is already being removed with the same or a more urgent mode
+    AlreadyRemoving(Idx<T, U>),
+    // `cancel_removal` was called for an agent which is not being removed
+    NotRemoving(Idx<T, U>),
+    // the node is out of the map or blocked for the kind of the agent
+    InvalidNode(N),
+    // `destinations[index]` has no nodes
+    EmptyDestination { index: usize },
+    InvalidDestination { index: usize, node: N },
+    // no node of `destinations[index]` can be reached from the start through the destinations before it
+    UnreachableDestination { index: usize },
+    // the map has no successor of the node for the movement index
+    NoMovement { idx: Idx<T, U>, node: N },
+    // a seat refused to be taken or released for the agent, the step stops in the middle and the simulator is poisoned
+    Seat { idx: Idx<T, U>, seat: S, error: SeatError },
+    // the allocator of the simulator has no index left below `U::max_value()`
+    IdxExhausted,
+    // `Simulator::add_with_idx` was given the index of a living agent
+    IdxInUse(Idx<T, U>),
+    // `Simulator::add_with_idx` was given `U::max_value()`
+    NullIdx,
+    // `Simulator::validate` failed after a step with the validation on
+    Invalid(Violation<S, T, U>),
+    // an earlier error left the simulator partially updated, restore a snapshot to continue
+    Poisoned,
+}
+
+impl<N: Clone, S: Clone, T, U: AgentIdxType> Clone for Error<N, S, T, U> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::UnknownAgent(idx) => Self::UnknownAgent(*idx),
+            Self::AlreadyRemoving(idx) => Self::AlreadyRemoving(*idx),
+            Self::NotRemoving(idx) => Self::NotRemoving(*idx),
+            Self::InvalidNode(node) => Self::InvalidNode(node.clone()),
+            Self::EmptyDestination { index } => Self::EmptyDestination { index: *index },
+            Self::InvalidDestination { index, node } => Self::InvalidDestination { index: *index, node: node.clone() },
+            Self::UnreachableDestination { index } => Self::UnreachableDestination { index: *index },
+            Self::NoMovement { idx, node } => Self::NoMovement { idx: *idx, node: node.clone() },
+            Self::Seat { idx, seat, error } => Self::Seat { idx: *idx, seat: seat.clone(), error: *error },
+            Self::IdxExhausted => Self::IdxExhausted,
+            Self::IdxInUse(idx) => Self::IdxInUse(*idx),
+            Self::NullIdx => Self::NullIdx,
+            Self::Invalid(v) => Self::Invalid(v.clone()),
+            Self::Poisoned => Self::Poisoned,
+        }
+    }
+}
+
+impl<N: PartialEq, S: PartialEq, T, U: AgentIdxType> PartialEq for Error<N, S, T, U> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::UnknownAgent(a), Self::UnknownAgent(b)) | (Self::AlreadyRemoving(a), Self::AlreadyRemoving(b)) | (Self::NotRemoving(a), Self::NotRemoving(b)) | (Self::IdxInUse(a), Self::IdxInUse(b)) => a == b,
+            (Self::InvalidNode(a), Self::InvalidNode(b)) => a == b,
+            (Self::EmptyDestination { index: a }, Self::EmptyDestination { index: b }) => a == b,
+            (Self::InvalidDestination { index: a, node: n }, Self::InvalidDestination { index: b, node: m }) => a == b && n == m,
+            (Self::UnreachableDestination { index: a }, Self::UnreachableDestination { index: b }) => a == b,
+            (Self::NoMovement { idx: a, node: n }, Self::NoMovement { idx: b, node: m }) => a == b && n == m,
+            (Self::Seat { idx: a, seat: s, error: e }, Self::Seat { idx: b, seat: t, error: f }) => a == b && s == t && e == f,
+            (Self::IdxExhausted, Self::IdxExhausted) | (Self::NullIdx, Self::NullIdx) | (Self::Poisoned, Self::Poisoned) => true,
+            (Self::Invalid(a), Self::Invalid(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl<N: Debug, S: Debug, T, U: AgentIdxType + Debug> Debug for Error<N, S, T, U> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownAgent(idx) => f.debug_tuple("UnknownAgent").field(idx).finish(),
+            Self::AlreadyRemoving(idx) => f.debug_tuple("AlreadyRemoving").field(idx).finish(),
+            Self::NotRemoving(idx) => f.debug_tuple("NotRemoving").field(idx).finish(),
+            Self::InvalidNode(node) => f.debug_tuple("InvalidNode").field(node).finish(),
+            Self::EmptyDestination { index } => f.debug_struct("EmptyDestination").field("index", index).finish(),
+            Self::InvalidDestination { index, node } => f.debug_struct("InvalidDestination").field("index", index).field("node", node).finish(),
+            Self::UnreachableDestination { index } => f.debug_struct("UnreachableDestination").field("index", index).finish(),
+            Self::NoMovement { idx, node } => f.debug_struct("NoMovement").field("idx", idx).field("node", node).finish(),
+            Self::Seat { idx, seat, error } => f.debug_struct("Seat").field("idx", idx).field("seat", seat).field("error", error).finish(),
+            Self::IdxExhausted => f.write_str("IdxExhausted"),
+            Self::IdxInUse(idx) => f.debug_tuple("IdxInUse").field(idx).finish(),
+            Self::NullIdx => f.write_str("NullIdx"),
+            Self::Invalid(v) => f.debug_tuple("Invalid").field(v).finish(),
+            Self::Poisoned => f.write_str("Poisoned"),
+        }
+    }
+}
+
+impl<N: Debug, S: Debug, T, U: AgentIdxType + Debug> Display for Error<N, S, T, U> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownAgent(idx) => write!(f, "agent {:?} does not exist", idx.value()),
+            Self::AlreadyRemoving(idx) => write!(f, "agent {:?} is already being removed at least as urgently", idx.value()),
+            Self::NotRemoving(idx) => write!(f, "agent {:?} is not being removed", idx.value()),
+            Self::InvalidNode(node) => write!(f, "node {:?} is out of the map or blocked", node),
+            Self::EmptyDestination { index } => write!(f, "destination {} has no nodes", index),
+            Self::InvalidDestination { index, node } => write!(f, "node {:?} of destination {} is out of the map or blocked", node, index),
+            Self::UnreachableDestination { index } => write!(f, "destination {} cannot be reached", index),
+            Self::NoMovement { idx, node } => write!(f, "agent {:?} has no such movement from node {:?}", idx.value(), node),
+            Self::Seat { idx, seat, error } => write!(f, "seat {:?} for agent {:?}: {}", seat, idx.value(), error),
+            Self::IdxExhausted => write!(f, "no index is left for a new agent, the maximum value is reserved as null"),
+            Self::IdxInUse(idx) => write!(f, "index {:?} is used by another agent", idx.value()),
+            Self::NullIdx => write!(f, "the maximum value of the index is reserved as null"),
+            Self::Invalid(v) => write!(f, "invalid state after a step: {}", v),
+            Self::Poisoned => write!(f, "the simulator was left partially updated by an earlier error"),
+        }
+    }
+}
+
+impl<N: Debug, S: Debug, T, U: AgentIdxType + Debug> std::error::Error for Error<N, S, T, U> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Seat { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
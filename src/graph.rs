@@ -0,0 +1,177 @@
+use std::{iter::{once, Once}, ops::{Index, IndexMut}, vec::IntoIter};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{index::index::Idx, map::{DummyHeuristic, Map}, seat::{AgentIdxType, Seat, SeatError}};
+
+// directed graph whose nodes are seats, nodes have coordinates only for display
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(serialize = "U: Serialize", deserialize = "U: Deserialize<'de>")))]
+pub struct GraphMap<U: AgentIdxType = u32, T = ()> {
+    nodes: Vec<GraphNode<T, U>>,
+}
+
+impl<U: AgentIdxType, T> GraphMap<U, T> {
+    pub fn new() -> Self {
+        Self { nodes: vec![] }
+    }
+
+    pub fn add_node(&mut self, x: f32, y: f32) -> usize {
+        self.nodes.push(GraphNode { x, y, nexts: vec![], occupant: None });
+        self.nodes.len() - 1
+    }
+
+    // returns false if either node does not exist
+    pub fn add_edge(&mut self, from: usize, to: usize, cost: u32) -> bool {
+        if from >= self.nodes.len() || to >= self.nodes.len() {
+            return false
+        }
+        self.nodes[from].nexts.push((to, cost));
+        true
+    }
+
+    pub fn len(&self) -> usize { self.nodes.len() }
+    pub fn is_empty(&self) -> bool { self.nodes.is_empty() }
+    pub fn nodes(&self) -> &Vec<GraphNode<T, U>> { &self.nodes }
+}
+
+impl<U: AgentIdxType, T> Default for GraphMap<U, T> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<U: AgentIdxType, T> Clone for GraphMap<U, T> {
+    fn clone(&self) -> Self {
+        Self { nodes: self.nodes.clone() }
+    }
+}
+
+impl<U: AgentIdxType, T> Map<U, T> for GraphMap<U, T> {
+    type Cost = u32;
+    type I = usize;
+    type SeatIndex = usize;
+    type Seat = GraphNode<T, U>;
+    type Node = usize;
+
+    type SIter = Once<Self::SeatIndex>;
+    type SCIter = IntoIter<(Self::I, Self::Node, Self::Cost)>;
+    type SBIter = Once<(Self::SeatIndex, Self::Cost)>;
+
+    type FH = DummyHeuristic;
+
+    fn seats(&self, &n: &Self::Node, _: &T) -> Self::SIter {
+        once(n)
+    }
+
+    fn successors(&self, &n: &Self::Node, _: &T) -> Self::SCIter {
+        self.nodes[n].nexts
+            .iter()
+            .enumerate()
+            .map(|(i, &(m, c))| (i, m, c))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    fn successor(&self, &n: &Self::Node, _: &T, &i: &Self::I) -> Option<Self::Node> {
+        self.nodes[n].nexts.get(i).map(|&(m, _)| m)
+    }
+
+    // the departing node is held until the arrival
+    fn seats_between(&self, &n: &Self::Node, _: &T, &i: &Self::I) -> Self::SBIter {
+        once((n, self.nodes[n].nexts[i].1))
+    }
+
+    fn has_node(&self, &n: &Self::Node, _: &T) -> bool {
+        n < self.nodes.len()
+    }
+}
+
+impl<U: AgentIdxType, T> Index<usize> for GraphMap<U, T> {
+    type Output = GraphNode<T, U>;
+
+    fn index(&self, i: usize) -> &Self::Output {
+        &self.nodes[i]
+    }
+}
+
+impl<U: AgentIdxType, T> IndexMut<usize> for GraphMap<U, T> {
+    fn index_mut(&mut self, i: usize) -> &mut Self::Output {
+        &mut self.nodes[i]
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(serialize = "U: Serialize", deserialize = "U: Deserialize<'de>")))]
+pub struct GraphNode<T, U: AgentIdxType> {
+    x: f32,
+    y: f32,
+    nexts: Vec<(usize, u32)>, // (index, cost)
+    occupant: Option<Idx<T, U>>,
+}
+
+impl<T, U: AgentIdxType> GraphNode<T, U> {
+    pub fn x(&self) -> f32 { self.x }
+    pub fn y(&self) -> f32 { self.y }
+    pub fn nexts(&self) -> &Vec<(usize, u32)> { &self.nexts }
+    pub fn occupant(&self) -> Option<Idx<T, U>> { self.occupant }
+}
+
+impl<T, U: AgentIdxType> Clone for GraphNode<T, U> {
+    fn clone(&self) -> Self {
+        Self { x: self.x, y: self.y, nexts: self.nexts.clone(), occupant: self.occupant }
+    }
+}
+
+impl<T, U: AgentIdxType> Seat<T, U> for GraphNode<T, U> {
+    fn is_empty_for(&self, idx: Idx<T, U>) -> bool {
+        self.occupant.is_none_or(|i| i == idx)
+    }
+
+    fn add(&mut self, idx: Idx<T, U>) -> Result<(), SeatError> {
+        if self.occupant.is_some_and(|i| i != idx) {
+            return Err(SeatError::AlreadyOccupied)
+        }
+        self.occupant = Some(idx);
+        Ok(())
+    }
+
+    fn remove(&mut self, idx: Idx<T, U>) -> Result<(), SeatError> {
+        if self.occupant != Some(idx) {
+            return Err(SeatError::NotHeld)
+        }
+        self.occupant = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{pathfind::common::MultipleEnds, simulator::{Simulator, StopReason}};
+
+    use super::GraphMap;
+
+    #[test]
+    fn test() {
+        // 0 -> 1 -> 2
+        //      ^    v
+        //      4 <- 3
+        let mut map = GraphMap::<u32>::new();
+        for (x, y) in [(0., 0.), (1., 0.), (2., 0.), (2., -1.), (1., -1.)] {
+            map.add_node(x, y);
+        }
+        for (i, j) in [(0, 1), (1, 2), (2, 3), (3, 4), (4, 1)] {
+            assert!(map.add_edge(i, j, 1));
+        }
+        assert!(!map.add_edge(0, 5, 1));
+
+        let mut s = Simulator::new(0, map, 3);
+        let i0 = s.add((), 0, VecDeque::from([MultipleEnds::new_as_all_zero(vec![4])])).unwrap();
+        let i1 = s.add((), 3, VecDeque::from([MultipleEnds::new_as_all_zero(vec![2])])).unwrap();
+
+        assert_eq!(s.run_until_idle(20).unwrap(), StopReason::Completed);
+        assert_eq!(s.agent(i0).map(|a| *a.current()), Some(4));
+        assert_eq!(s.agent(i1).map(|a| *a.current()), Some(2));
+    }
+}
@@ -0,0 +1,272 @@
+use std::{collections::{BTreeMap, HashMap, VecDeque}, fmt::{Display, Formatter}, hash::Hash, str::FromStr};
+
+use crate::{agent_data::AgentState, index::index::Idx, pathfind::common::MultipleEnds, seat::AgentIdxType, simulator::Simulator, Error};
+
+use super::map::GridMap;
+
+//
+// 3 .  .  #  *0
+// 2 .  .  #  +0
+// 1 1  .  .  >0
+// 0 .  .  .  0
+//   0  1  2  3
+//
+// (axis labels are not part of the format)
+//
+// n: current node of agent n
+// >n: next node of agent n
+// +n: other seat reserved by agent n
+// *n: destination of agent n
+// #: blocked, .: free
+//
+// blocked cells are always shown as #, otherwise when several apply to a cell the one listed first is shown
+// only the next destination of each agent is shown, a destination shared by agents is shown for the smallest index
+
+// grid map with occupancy only
+impl<U: AgentIdxType + Display, T> Display for GridMap<U, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let cells = self.occupied_cells();
+        write_cells(f, self, &cells)
+    }
+}
+
+impl<U: AgentIdxType, T> GridMap<U, T> {
+    fn occupied_cells(&self) -> HashMap<(usize, usize), String> where U: Display {
+        (0..self.nx())
+            .flat_map(|x| (0..self.ny()).map(move |y| (x, y)))
+            .filter_map(|p| self.occupant(p).map(|i| (p, format!("+{}", i.value()))))
+            .collect()
+    }
+}
+
+pub struct GridDisplay<'a, U: AgentIdxType + Ord, T = ()>(&'a Simulator<GridMap<U, T>, U, T>);
+
+impl<'a, U: AgentIdxType + Ord, T> GridDisplay<'a, U, T> {
+    pub fn new(s: &'a Simulator<GridMap<U, T>, U, T>) -> Self { Self(s) }
+}
+
+impl<U: AgentIdxType + Ord + Hash + Display, T> Display for GridDisplay<'_, U, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = self.0;
+
+        // lowest priority first, later ones overwrite
+        let mut cells = HashMap::new();
+        for (i, a) in s.agents().iter().rev() {
+            for &p in a.next_destinations().into_iter().flat_map(|d| d.ends().keys()) {
+                cells.insert(p, format!("*{}", i.value()));
+            }
+        }
+        cells.extend(s.map().occupied_cells());
+        for (i, a) in s.agents() {
+            if let AgentState::Moving { nexts } = a.state() {
+                cells.insert(nexts[0].0, format!(">{}", i.value()));
+            }
+        }
+        for placed in [false, true] {
+            for (i, a) in s.agents() {
+                if (*a.state() != AgentState::NotPlaced) == placed {
+                    cells.insert(*a.current(), format!("{}", i.value()));
+                }
+            }
+        }
+
+        write_cells(f, s.map(), &cells)
+    }
+}
+
+fn write_cells<U: AgentIdxType, T>(f: &mut Formatter<'_>, map: &GridMap<U, T>, cells: &HashMap<(usize, usize), String>) -> std::fmt::Result {
+    let width = cells.values().map(|c| c.len()).max().unwrap_or(1);
+    for y in (0..map.ny()).rev() {
+        let row = (0..map.nx())
+            .map(|x| {
+                let cell = if map.is_blocked((x, y)) { "#" } else { cells.get(&(x, y)).map_or(".", |c| c.as_str()) };
+                format!("{:width$}", cell, width = width)
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        writeln!(f, "{}", row.trim_end())?;
+    }
+    Ok(())
+}
+
+#[allow(clippy::type_complexity)]
+pub struct GridScenario<U: AgentIdxType = u32, T = ()> {
+    pub map: GridMap<U, T>,
+    // agent -> (start, destinations)
+    pub agents: BTreeMap<U, ((usize, usize), Vec<(usize, usize)>)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GridParseError {
+    Empty,
+    // rows must have the same number of cells
+    UnevenRow { line: usize },
+    InvalidCell { line: usize, cell: String },
+    DuplicateAgent { line: usize, cell: String },
+    // a destination without the current node
+    MissingAgent { agent: String },
+}
+
+impl Display for GridParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "the diagram is empty"),
+            Self::UnevenRow { line } => write!(f, "line {} has a different number of cells", line),
+            Self::InvalidCell { line, cell } => write!(f, "invalid cell '{}' at line {}", cell, line),
+            Self::DuplicateAgent { line, cell } => write!(f, "agent '{}' at line {} appears twice", cell, line),
+            Self::MissingAgent { agent } => write!(f, "agent {} has a destination but no current node", agent),
+        }
+    }
+}
+
+impl std::error::Error for GridParseError {}
+
+impl<U: AgentIdxType + Ord + FromStr + Display, T> FromStr for GridScenario<U, T> {
+    type Err = GridParseError;
+
+    // +n and >n are read as free cells so that a rendered state can be parsed back
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows = s.lines()
+            .enumerate()
+            .map(|(l, row)| (l + 1, row.split_whitespace().collect::<Vec<_>>()))
+            .filter(|(_, row)| !row.is_empty())
+            .collect::<Vec<_>>();
+        let Some((_, first)) = rows.first() else { return Err(GridParseError::Empty) };
+        let (nx, ny) = (first.len(), rows.len());
+
+        let mut map = GridMap::new(nx, ny);
+        let mut starts = BTreeMap::new();
+        let mut destinations: BTreeMap<U, Vec<(usize, usize)>> = BTreeMap::new();
+        for (k, (line, row)) in rows.iter().enumerate() {
+            if row.len() != nx {
+                return Err(GridParseError::UnevenRow { line: *line })
+            }
+            let y = ny - 1 - k;
+            for (x, &cell) in row.iter().enumerate() {
+                let invalid = || GridParseError::InvalidCell { line: *line, cell: cell.to_string() };
+                match cell {
+                    "." => {},
+                    "#" => map.set_blocked((x, y), true),
+                    _ => match cell.split_at(cell.len() - cell.trim_start_matches(['*', '+', '>']).len()) {
+                        ("*", i) => destinations.entry(i.parse().map_err(|_| invalid())?).or_default().push((x, y)),
+                        ("+" | ">", i) => { i.parse::<U>().map_err(|_| invalid())?; },
+                        ("", i) => {
+                            if starts.insert(i.parse().map_err(|_| invalid())?, (x, y)).is_some() {
+                                return Err(GridParseError::DuplicateAgent { line: *line, cell: cell.to_string() })
+                            }
+                        },
+                        _ => return Err(invalid()),
+                    },
+                }
+            }
+        }
+
+        if let Some(i) = destinations.keys().find(|&i| !starts.contains_key(i)) {
+            return Err(GridParseError::MissingAgent { agent: i.to_string() })
+        }
+
+        let agents = starts.into_iter()
+            .map(|(i, p)| (i, (p, destinations.remove(&i).unwrap_or_default())))
+            .collect();
+        Ok(Self { map, agents })
+    }
+}
+
+impl<U: AgentIdxType + Ord + Hash, T: Default> GridScenario<U, T> {
+    // agents are added in the order of their indices, the returned map translates the indices in the diagram
+    #[allow(clippy::type_complexity)]
+    pub fn into_simulator(self, init_time: u32, max_reservation_time: u32) -> Result<(Simulator<GridMap<U, T>, U, T>, BTreeMap<U, Idx<T, U>>), Error<(usize, usize), (usize, usize), T, U>> {
+        let mut s = Simulator::new(init_time, self.map, max_reservation_time);
+        let idxs = self.agents.into_iter()
+            .map(|(i, (start, ends))| {
+                let destinations = if ends.is_empty() { VecDeque::new() } else { VecDeque::from([MultipleEnds::new_as_all_zero(ends)]) };
+                Ok((i, s.add(T::default(), start, destinations)?))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok((s, idxs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{grid::map::GridMap, pathfind::common::MultipleEnds, simulator::StopReason};
+
+    use super::{GridDisplay, GridParseError, GridScenario};
+
+    #[test]
+    fn parse_test() {
+        let scenario: GridScenario = "
+            .  .  #  *0
+            .  .  #  .
+            1  .  .  .
+            *1 .  .  0
+        ".parse().unwrap();
+
+        assert_eq!((scenario.map.nx(), scenario.map.ny()), (4, 4));
+        assert!(scenario.map.is_blocked((2, 3)) && scenario.map.is_blocked((2, 2)) && !scenario.map.is_blocked((2, 1)));
+        assert_eq!(scenario.agents[&0], ((3, 0), vec![(3, 3)]));
+        assert_eq!(scenario.agents[&1], ((0, 1), vec![(0, 0)]));
+
+        assert_eq!("".parse::<GridScenario>().err(), Some(GridParseError::Empty));
+        assert_eq!(". .\n.".parse::<GridScenario>().err(), Some(GridParseError::UnevenRow { line: 2 }));
+        assert_eq!(". a".parse::<GridScenario>().err(), Some(GridParseError::InvalidCell { line: 1, cell: "a".to_string() }));
+        assert_eq!("0 0".parse::<GridScenario>().err(), Some(GridParseError::DuplicateAgent { line: 1, cell: "0".to_string() }));
+        assert_eq!(". *3".parse::<GridScenario>().err(), Some(GridParseError::MissingAgent { agent: "3".to_string() }));
+    }
+
+    #[test]
+    fn display_test() {
+        let scenario: GridScenario = "
+            . . # *0
+            . . # .
+            . . . .
+            . . . 0
+        ".parse().unwrap();
+        let (mut s, _) = scenario.into_simulator(0, 2).unwrap();
+
+        assert_eq!(GridDisplay::new(&s).to_string(), ".  .  #  *0\n.  .  #  .\n.  .  .  .\n.  .  .  0\n");
+        s.step().unwrap();
+        assert_eq!(GridDisplay::new(&s).to_string(), ".  .  #  *0\n.  .  #  +0\n.  .  .  >0\n.  .  .  0\n");
+        assert_eq!(s.map().to_string(), ".  .  #  .\n.  .  #  +0\n.  .  .  +0\n.  .  .  +0\n");
+
+        // a rendered state is parsed back as a scenario
+        let scenario: GridScenario = GridDisplay::new(&s).to_string().parse().unwrap();
+        assert_eq!(scenario.agents[&0], ((3, 0), vec![(3, 3)]));
+
+        assert_eq!(s.run_until_idle(20).unwrap(), StopReason::Completed);
+        assert_eq!(GridDisplay::new(&s).to_string(), ". . # 0\n. . # .\n. . . .\n. . . .\n");
+    }
+
+    #[test]
+    fn destination_display_test() {
+        let scenario: GridScenario = "
+            . . .
+            0 . 1
+        ".parse().unwrap();
+        let (mut s, idxs) = scenario.into_simulator(0, 2).unwrap();
+        *s.agent_destination_mut(idxs[&0]).unwrap() = VecDeque::from([
+            MultipleEnds::new_as_all_zero(vec![(1, 1)]),
+            MultipleEnds::new_as_all_zero(vec![(2, 1)]),
+        ]);
+        *s.agent_destination_mut(idxs[&1]).unwrap() = VecDeque::from([MultipleEnds::new_as_all_zero(vec![(1, 1), (1, 0)])]);
+
+        assert_eq!(GridDisplay::new(&s).to_string(), ".  *0 .\n0  *1 1\n");
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let text = "\
+1  .  .  *2
+#  #  .  #
+0  *0 2  *1
+";
+        let scenario: GridScenario = text.parse().unwrap();
+        let (s, idxs) = scenario.into_simulator(0, 3).unwrap();
+
+        assert_eq!(idxs.len(), 3);
+        assert_eq!(GridDisplay::new(&s).to_string(), text);
+        assert_eq!(GridMap::<u32>::new(2, 1).to_string(), ". .\n");
+    }
+}
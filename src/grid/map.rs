@@ -0,0 +1,175 @@
+use std::{iter::{once, Once}, ops::{Index, IndexMut}, vec::IntoIter};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{index::index::Idx, map::{Heuristic, Map}, pathfind::common::MultipleEnds, seat::{AgentIdxType, Seat, SeatError}};
+
+// 4-connected grid with unit costs, indexed by (x, y)
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(serialize = "U: Serialize", deserialize = "U: Deserialize<'de>")))]
+pub struct GridMap<U: AgentIdxType = u32, T = ()> {
+    cells: Vec<Vec<GridCell<T, U>>>,
+}
+
+impl<U: AgentIdxType, T> GridMap<U, T> {
+    pub fn new(nx: usize, ny: usize) -> Self {
+        Self { cells: (0..nx).map(|_| (0..ny).map(|_| GridCell::new()).collect()).collect() }
+    }
+
+    pub fn new_with_blocked(nx: usize, ny: usize, blocked: &[(usize, usize)]) -> Self {
+        let mut map = Self::new(nx, ny);
+        for &p in blocked {
+            map.set_blocked(p, true);
+        }
+        map
+    }
+
+    pub fn nx(&self) -> usize { self.cells.len() }
+    pub fn ny(&self) -> usize { self.cells.first().map_or(0, |c| c.len()) }
+
+    pub fn contains(&self, (x, y): (usize, usize)) -> bool { x < self.nx() && y < self.ny() }
+    pub fn is_blocked(&self, p: (usize, usize)) -> bool { self[p].blocked }
+    pub fn set_blocked(&mut self, p: (usize, usize), blocked: bool) { self[p].blocked = blocked }
+    pub fn occupant(&self, p: (usize, usize)) -> Option<Idx<T, U>> { self[p].occupant }
+}
+
+impl<U: AgentIdxType, T> Clone for GridMap<U, T> {
+    fn clone(&self) -> Self {
+        Self { cells: self.cells.clone() }
+    }
+}
+
+const DXYS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+impl<U: AgentIdxType, T> Map<U, T> for GridMap<U, T> {
+    type Cost = u32;
+    type I = usize;
+    type SeatIndex = (usize, usize);
+    type Seat = GridCell<T, U>;
+    type Node = (usize, usize);
+
+    type SIter = Once<Self::SeatIndex>;
+    type SCIter = IntoIter<(Self::I, Self::Node, Self::Cost)>;
+    type SBIter = Once<(Self::SeatIndex, Self::Cost)>;
+
+    type FH = GridHeuristic;
+
+    fn seats(&self, n: &Self::Node, _: &T) -> Self::SIter {
+        once(*n)
+    }
+
+    fn successors(&self, n: &Self::Node, t: &T) -> Self::SCIter {
+        (0..DXYS.len())
+            .filter_map(|i| self.successor(n, t, &i).map(|m| (i, m, 1)))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    fn successor(&self, &(x, y): &Self::Node, _: &T, &i: &Self::I) -> Option<Self::Node> {
+        let (dx, dy) = DXYS.get(i)?;
+        let (x, y) = (x.checked_add_signed(*dx as isize)?, y.checked_add_signed(*dy as isize)?);
+        if self.contains((x, y)) && !self.is_blocked((x, y)) {
+            Some((x, y))
+        } else {
+            None
+        }
+    }
+
+    // the departing cell is held until the arrival
+    fn seats_between(&self, n: &Self::Node, _: &T, _: &Self::I) -> Self::SBIter {
+        once((*n, 1))
+    }
+
+    fn heuristic(&self, dests: &MultipleEnds<Self::Node, Self::Cost>) -> Option<Self::FH> {
+        GridHeuristic::new(dests)
+    }
+
+    fn has_node(&self, &n: &Self::Node, _: &T) -> bool {
+        self.contains(n) && !self.is_blocked(n)
+    }
+}
+
+impl<U: AgentIdxType, T> Index<(usize, usize)> for GridMap<U, T> {
+    type Output = GridCell<T, U>;
+
+    fn index(&self, (x, y): (usize, usize)) -> &Self::Output {
+        &self.cells[x][y]
+    }
+}
+
+impl<U: AgentIdxType, T> IndexMut<(usize, usize)> for GridMap<U, T> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Self::Output {
+        &mut self.cells[x][y]
+    }
+}
+
+// manhattan distance to the nearest end
+pub struct GridHeuristic {
+    ends: Vec<(usize, usize)>,
+}
+
+impl GridHeuristic {
+    pub fn new(dests: &MultipleEnds<(usize, usize), u32>) -> Option<Self> {
+        if dests.is_empty() {
+            None
+        } else {
+            Some(Self { ends: dests.ends().keys().copied().collect() })
+        }
+    }
+}
+
+impl Heuristic<(usize, usize), u32> for GridHeuristic {
+    fn heuristic(&self, &(x, y): &(usize, usize)) -> u32 {
+        self.ends.iter().map(|&(x0, y0)| (x0.abs_diff(x) + y0.abs_diff(y)) as u32).min().unwrap_or(0)
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(serialize = "U: Serialize", deserialize = "U: Deserialize<'de>")))]
+pub struct GridCell<T, U: AgentIdxType> {
+    blocked: bool,
+    occupant: Option<Idx<T, U>>,
+}
+
+impl<T, U: AgentIdxType> GridCell<T, U> {
+    fn new() -> Self {
+        Self { blocked: false, occupant: None }
+    }
+
+    pub fn is_blocked(&self) -> bool { self.blocked }
+    pub fn occupant(&self) -> Option<Idx<T, U>> { self.occupant }
+}
+
+impl<T, U: AgentIdxType> Clone for GridCell<T, U> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, U: AgentIdxType> Copy for GridCell<T, U> {}
+
+impl<T, U: AgentIdxType> Seat<T, U> for GridCell<T, U> {
+    fn is_empty_for(&self, idx: Idx<T, U>) -> bool {
+        !self.blocked && self.occupant.is_none_or(|i| i == idx)
+    }
+
+    fn add(&mut self, idx: Idx<T, U>) -> Result<(), SeatError> {
+        if self.blocked {
+            return Err(SeatError::Blocked)
+        }
+        if self.occupant.is_some_and(|i| i != idx) {
+            return Err(SeatError::AlreadyOccupied)
+        }
+        self.occupant = Some(idx);
+        Ok(())
+    }
+
+    fn remove(&mut self, idx: Idx<T, U>) -> Result<(), SeatError> {
+        if self.occupant != Some(idx) {
+            return Err(SeatError::NotHeld)
+        }
+        self.occupant = None;
+        Ok(())
+    }
+}
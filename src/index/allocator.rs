@@ -0,0 +1,279 @@
+use std::{collections::BTreeMap, fmt::{Debug, Formatter}};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::seat::AgentIdxType;
+
+use super::index::Idx;
+
+// indices of the agents in a simulator
+pub trait IdxSet<U> {
+    fn contains(&self, idx: U) -> bool;
+    fn first(&self) -> Option<U>;
+    fn last(&self) -> Option<U>;
+}
+
+impl<T, U: AgentIdxType + Ord, V> IdxSet<U> for BTreeMap<Idx<T, U>, V> {
+    fn contains(&self, idx: U) -> bool { self.contains_key(&Idx::new(idx)) }
+    fn first(&self) -> Option<U> { self.first_key_value().map(|(i, _)| i.value()) }
+    fn last(&self) -> Option<U> { self.last_key_value().map(|(i, _)| i.value()) }
+}
+
+// chooses the index of an agent added to a simulator
+// `U::max_value()` is never returned since seats use it as null
+pub trait IdxAllocator<U>: Send + Sync {
+    // an index not in `living`, None if no index is left
+    fn allocate(&mut self, living: &dyn IdxSet<U>) -> Option<U>;
+    // the agent of `idx` has left the simulator
+    fn release(&mut self, _idx: U) {}
+    fn clone_box(&self) -> Box<dyn IdxAllocator<U>>;
+}
+
+impl<U> Clone for Box<dyn IdxAllocator<U>> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+// the allocator held by a simulator, the built-in ones are kept in snapshots with their states
+// a custom one cannot be serialized, a deserialized simulator counts up from its largest index instead
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+    bound(serialize = "U: AgentIdxType + Serialize", deserialize = "U: Deserialize<'de>"),
+    into = "BuiltinAllocator<U>",
+    from = "BuiltinAllocator<U>",
+))]
+pub enum Allocator<U> {
+    Gap(GapAllocator),
+    Monotonic(MonotonicAllocator<U>),
+    FreeList(FreeListAllocator<U>),
+    Custom(Box<dyn IdxAllocator<U>>),
+}
+
+impl<U: Debug> Debug for Allocator<U> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Gap(a) => f.debug_tuple("Gap").field(a).finish(),
+            Self::Monotonic(a) => f.debug_tuple("Monotonic").field(a).finish(),
+            Self::FreeList(a) => f.debug_tuple("FreeList").field(a).finish(),
+            Self::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+// the serialized form of `Allocator`
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Allocator")]
+enum BuiltinAllocator<U> {
+    Gap(GapAllocator),
+    Monotonic(MonotonicAllocator<U>),
+    FreeList(FreeListAllocator<U>),
+}
+
+#[cfg(feature = "serde")]
+impl<U: AgentIdxType> From<Allocator<U>> for BuiltinAllocator<U> {
+    fn from(a: Allocator<U>) -> Self {
+        match a {
+            Allocator::Gap(a) => Self::Gap(a),
+            Allocator::Monotonic(a) => Self::Monotonic(a),
+            Allocator::FreeList(a) => Self::FreeList(a),
+            Allocator::Custom(_) => Self::Monotonic(MonotonicAllocator::default()),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<U> From<BuiltinAllocator<U>> for Allocator<U> {
+    fn from(a: BuiltinAllocator<U>) -> Self {
+        match a {
+            BuiltinAllocator::Gap(a) => Self::Gap(a),
+            BuiltinAllocator::Monotonic(a) => Self::Monotonic(a),
+            BuiltinAllocator::FreeList(a) => Self::FreeList(a),
+        }
+    }
+}
+
+impl<U: AgentIdxType> Default for Allocator<U> {
+    fn default() -> Self {
+        Self::Monotonic(MonotonicAllocator::default())
+    }
+}
+
+impl<U> From<GapAllocator> for Allocator<U> {
+    fn from(a: GapAllocator) -> Self { Self::Gap(a) }
+}
+
+impl<U> From<MonotonicAllocator<U>> for Allocator<U> {
+    fn from(a: MonotonicAllocator<U>) -> Self { Self::Monotonic(a) }
+}
+
+impl<U> From<FreeListAllocator<U>> for Allocator<U> {
+    fn from(a: FreeListAllocator<U>) -> Self { Self::FreeList(a) }
+}
+
+impl<U> From<Box<dyn IdxAllocator<U>>> for Allocator<U> {
+    fn from(a: Box<dyn IdxAllocator<U>>) -> Self { Self::Custom(a) }
+}
+
+impl<U: AgentIdxType + Ord> IdxAllocator<U> for Allocator<U> {
+    fn allocate(&mut self, living: &dyn IdxSet<U>) -> Option<U> {
+        match self {
+            Self::Gap(a) => a.allocate(living),
+            Self::Monotonic(a) => a.allocate(living),
+            Self::FreeList(a) => a.allocate(living),
+            Self::Custom(a) => a.allocate(living),
+        }
+    }
+
+    fn release(&mut self, idx: U) {
+        match self {
+            Self::Gap(a) => a.release(idx),
+            Self::Monotonic(a) => a.release(idx),
+            Self::FreeList(a) => a.release(idx),
+            Self::Custom(a) => a.release(idx),
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn IdxAllocator<U>> { Box::new(self.clone()) }
+}
+
+// below the smallest or above the largest living index, removed indices are reused
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GapAllocator;
+
+impl<U: AgentIdxType + Ord> IdxAllocator<U> for GapAllocator {
+    fn allocate(&mut self, living: &dyn IdxSet<U>) -> Option<U> {
+        let (Some(first), Some(last)) = (living.first(), living.last()) else { return Some(U::zero()) };
+        if first != U::zero() {
+            return Some(first - U::one())
+        }
+        Some(last + U::one()).filter(|&i| i != U::max_value())
+    }
+
+    fn clone_box(&self) -> Box<dyn IdxAllocator<U>> { Box::new(*self) }
+}
+
+// counts up and never reuses an index, so a handle of a removed agent refers to nothing
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MonotonicAllocator<U> {
+    next: U,
+}
+
+impl<U: AgentIdxType> Default for MonotonicAllocator<U> {
+    fn default() -> Self {
+        Self { next: U::zero() }
+    }
+}
+
+impl<U: AgentIdxType + Ord> IdxAllocator<U> for MonotonicAllocator<U> {
+    fn allocate(&mut self, living: &dyn IdxSet<U>) -> Option<U> {
+        // jumps over the living indices in case the counter is behind them, e.g. after deserialization
+        let i = living.last().map_or(self.next, |l| self.next.max(l + U::one()));
+        if i == U::max_value() {
+            return None
+        }
+        self.next = i + U::one();
+        Some(i)
+    }
+
+    fn clone_box(&self) -> Box<dyn IdxAllocator<U>> { Box::new(*self) }
+}
+
+// reuses the most recently released index first, counts up when none is left
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FreeListAllocator<U> {
+    free: Vec<U>,
+    next: U,
+}
+
+impl<U: AgentIdxType> Default for FreeListAllocator<U> {
+    fn default() -> Self {
+        Self { free: vec![], next: U::zero() }
+    }
+}
+
+impl<U: AgentIdxType + Ord> IdxAllocator<U> for FreeListAllocator<U> {
+    fn allocate(&mut self, living: &dyn IdxSet<U>) -> Option<U> {
+        // released indices may have been taken by `Simulator::add_with_idx` since
+        while let Some(i) = self.free.pop() {
+            if !living.contains(i) {
+                return Some(i)
+            }
+        }
+        let mut i = self.next;
+        while living.contains(i) {
+            i = i + U::one();
+        }
+        if i == U::max_value() {
+            return None
+        }
+        self.next = i + U::one();
+        Some(i)
+    }
+
+    // indices above the counter are handed out by counting up
+    fn release(&mut self, idx: U) {
+        if idx < self.next {
+            self.free.push(idx);
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn IdxAllocator<U>> { Box::new(self.clone()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::index::index::Idx;
+
+    use super::{FreeListAllocator, GapAllocator, IdxAllocator, MonotonicAllocator};
+
+    fn living(idxs: &[u8]) -> BTreeMap<Idx<(), u8>, ()> {
+        idxs.iter().map(|&i| (Idx::new(i), ())).collect()
+    }
+
+    #[test]
+    fn gap_test() {
+        let mut a = GapAllocator;
+        assert_eq!(a.allocate(&living(&[])), Some(0));
+        assert_eq!(a.allocate(&living(&[2, 5])), Some(1));
+        assert_eq!(a.allocate(&living(&[0, 5])), Some(6));
+        assert_eq!(a.allocate(&living(&[0, 254])), None);
+    }
+
+    #[test]
+    fn monotonic_test() {
+        let mut a = MonotonicAllocator::default();
+        assert_eq!(a.allocate(&living(&[])), Some(0));
+        assert_eq!(a.allocate(&living(&[0])), Some(1));
+        // removed indices are not reused
+        assert_eq!(a.allocate(&living(&[])), Some(2));
+        assert_eq!(a.allocate(&living(&[7])), Some(8));
+        assert_eq!(a.allocate(&living(&[253])), Some(254));
+        assert_eq!(a.allocate(&living(&[])), None);
+    }
+
+    #[test]
+    fn free_list_test() {
+        let mut a = FreeListAllocator::default();
+        assert_eq!(a.allocate(&living(&[])), Some(0));
+        assert_eq!(a.allocate(&living(&[0])), Some(1));
+        assert_eq!(a.allocate(&living(&[0, 1, 2])), Some(3));
+        a.release(1);
+        a.release(0);
+        assert_eq!(a.allocate(&living(&[2, 3])), Some(0));
+        // taken by another way meanwhile
+        assert_eq!(a.allocate(&living(&[1, 2, 3])), Some(4));
+        // indices above the counter are never released into the list
+        a.release(100);
+        assert_eq!(a.allocate(&living(&[1, 2, 3, 4])), Some(5));
+        assert_eq!(a.allocate(&living(&(0..255).collect::<Vec<_>>())), None);
+    }
+}
@@ -1,6 +1,8 @@
 use std::{any::type_name, cmp::Ordering, fmt::{Debug, Formatter}, hash::{Hash, Hasher}, marker::PhantomData};
 
 use num_traits::Unsigned;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use trait_set::trait_set;
 
 trait_set! {
@@ -55,12 +57,26 @@ impl<T, U: IdxType + Debug> Debug for Idx<T, U> {
 
 impl<T, U: IdxType> Clone for Idx<T, U> {
     fn clone(&self) -> Self {
-        Self { idx: self.idx, _p: PhantomData }
+        *self
     }
 }
 
 impl<T, U: IdxType> Copy for Idx<T, U> {}
 
+#[cfg(feature = "serde")]
+impl<T, U: IdxType + Serialize> Serialize for Idx<T, U> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.idx.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T, U: IdxType + Deserialize<'de>> Deserialize<'de> for Idx<T, U> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        U::deserialize(deserializer).map(Self::new)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::Idx;
@@ -77,4 +93,13 @@ mod tests {
         assert!(i != k);
         assert!(i < k);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_test() {
+        let i = Idx::<T, u32>::new(7);
+
+        assert_eq!(serde_json::to_string(&i).unwrap(), "7");
+        assert_eq!(serde_json::from_str::<Idx<T, u32>>("7").unwrap(), i);
+    }
 }
@@ -1,2 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod index;
 pub mod nullable;
+pub mod allocator;
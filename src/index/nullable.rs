@@ -1,7 +1,9 @@
 use num_traits::bounds::UpperBounded;
+#[cfg(feature = "serde")]
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
 use std::{fmt::{Debug, Formatter}, hash::{Hash, Hasher}};
 
-use crate::seat::AgentIdxType;
+use crate::seat::{AgentIdxType, Seat, SeatError};
 
 use super::index::{Idx, IdxType};
 
@@ -38,20 +40,135 @@ impl<T, U: AgentIdxType + Hash> Hash for Nullable<T, U> {
     }
 }
 
+impl<T, U: AgentIdxType> PartialEq for Nullable<T, U> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T, U: AgentIdxType> Eq for Nullable<T, U> {}
+
+impl<T, U: AgentIdxType> Default for Nullable<T, U> {
+    fn default() -> Self {
+        Self::new_null()
+    }
+}
+
 impl<T, U: AgentIdxType + Debug> Debug for Nullable<T, U> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         if self.is_null() {
-            f.write_str("Idx {{ Null }}")
+            f.write_str("Idx { Null }")
         } else {
-            f.write_str(format!("{:?}", self).as_str())
+            Debug::fmt(&self.value, f)
         }
     }
 }
 
 impl<T, U: AgentIdxType> Clone for Nullable<T, U> {
     fn clone(&self) -> Self {
-        Self { value: self.value }
+        *self
     }
 }
 
 impl<T, U: AgentIdxType> Copy for Nullable<T, U> {}
+
+// a seat held by at most one agent, null while empty
+impl<T, U: AgentIdxType> Seat<T, U> for Nullable<T, U> {
+    fn is_empty_for(&self, idx: Idx<T, U>) -> bool {
+        self.is_null() || self.value == idx
+    }
+
+    fn add(&mut self, idx: Idx<T, U>) -> Result<(), SeatError> {
+        if !self.is_empty_for(idx) {
+            return Err(SeatError::AlreadyOccupied)
+        }
+        self.value = idx;
+        Ok(())
+    }
+
+    fn remove(&mut self, idx: Idx<T, U>) -> Result<(), SeatError> {
+        if self.is_null() || self.value != idx {
+            return Err(SeatError::NotHeld)
+        }
+        *self = Self::new_null();
+        Ok(())
+    }
+
+    fn is_held_by(&self, idx: Idx<T, U>) -> bool {
+        !self.is_null() && self.value == idx
+    }
+}
+
+// serialized as `Option<U>`
+#[cfg(feature = "serde")]
+impl<T, U: AgentIdxType + Serialize> Serialize for Nullable<T, U> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value().map(|i| i.value()).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T, U: AgentIdxType + Deserialize<'de>> Deserialize<'de> for Nullable<T, U> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Option::<U>::deserialize(deserializer)? {
+            None => Ok(Self::new_null()),
+            Some(v) if v == U::max_value() => Err(D::Error::custom("the maximum value is reserved as null")),
+            Some(v) => Ok(Self::new(v)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{index::index::Idx, seat::{Seat, SeatError}};
+
+    use super::Nullable;
+
+    struct T {}
+
+    #[test]
+    fn test() {
+        let (i, j) = (Nullable::<T, u32>::new(3), Nullable::<T, u32>::new_null());
+
+        assert!(!i.is_null());
+        assert_eq!(i.value().unwrap().value(), 3);
+        assert!(j.is_null());
+        assert!(j.value().is_none());
+        assert_eq!(j, Nullable::default());
+        assert_ne!(i, j);
+    }
+
+    #[test]
+    fn debug_test() {
+        assert_eq!(format!("{:?}", Nullable::<T, u32>::new_null()), "Idx { Null }");
+        assert_eq!(format!("{:?}", Nullable::<T, u32>::new(3)), format!("{:?}", Idx::<T, u32>::new(3)));
+    }
+
+    #[test]
+    fn seat_test() {
+        let (i0, i1) = (Idx::new(0), Idx::new(1));
+        let mut seat = Nullable::<T, u32>::new_null();
+
+        assert!(seat.is_empty_for(i0) && !seat.is_held_by(i0));
+        assert_eq!(seat.add(i0), Ok(()));
+        assert_eq!(seat.add(i0), Ok(()));
+        assert!(seat.is_held_by(i0) && !seat.is_empty_for(i1));
+        assert_eq!(seat.add(i1), Err(SeatError::AlreadyOccupied));
+        assert_eq!(seat.remove(i1), Err(SeatError::NotHeld));
+        assert_eq!(seat.remove(i0), Ok(()));
+        assert!(seat.is_null());
+        assert_eq!(seat.remove(i0), Err(SeatError::NotHeld));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_test() {
+        let (i, j) = (Nullable::<T, u32>::new(3), Nullable::<T, u32>::new_null());
+
+        assert_eq!(serde_json::to_string(&i).unwrap(), "3");
+        assert_eq!(serde_json::to_string(&j).unwrap(), "null");
+        assert_eq!(serde_json::from_str::<Nullable<T, u32>>("3").unwrap().value(), i.value());
+        assert!(serde_json::from_str::<Nullable<T, u32>>("null").unwrap().is_null());
+        assert!(serde_json::from_str::<Nullable<T, u32>>(&u32::MAX.to_string()).is_err());
+    }
+}
@@ -1,7 +1,23 @@
 pub mod agent_data;
+pub mod cost;
 pub mod duration;
+pub mod error;
 pub mod index;
 pub mod simulator;
 pub mod seat;
+pub mod store;
 pub mod pathfind;
 pub mod map;
+pub mod grid;
+pub mod graph;
+pub mod metrics;
+#[cfg(feature = "replay")]
+pub mod replay;
+#[cfg(feature = "trajectory")]
+pub mod trajectory;
+#[cfg(feature = "scenario")]
+pub mod scenario;
+#[cfg(feature = "svg")]
+pub mod svg;
+
+pub use error::Error;
@@ -1,6 +1,8 @@
 use std::{hash::Hash, ops::IndexMut};
 
 use num_traits::One;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use crate::{pathfind::common::{Cost, MultipleEnds, Node, Seat as TSeat}, seat::{AgentIdxType, Seat}};
 
@@ -23,6 +25,9 @@ pub trait Map<U: AgentIdxType, T = ()>: IndexMut<Self::SeatIndex, Output = Self:
 
     fn heuristic(&self, _dest: &MultipleEnds<Self::Node, Self::Cost>) -> Option<Self::FH> { None }
 
+    // whether an agent of kind `t` can stand on `n`
+    fn has_node(&self, _n: &Self::Node, _t: &T) -> bool { true }
+
     fn movement(&self, n: &Self::Node, t: &T, i: &Self::I) -> Option<Movement<Self, U, T>> where Self: Sized {
         let node = self.successor(n, t, i)?;
         let seats_between = self.seats_between(n, t, i)
@@ -44,6 +49,11 @@ impl<N: Node, C: Cost> Heuristic<N, C> for DummyHeuristic {
 }
 
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(
+    serialize = "M::Node: Serialize, M::SeatIndex: Serialize, M::Cost: Serialize",
+    deserialize = "M::Node: Deserialize<'de>, M::SeatIndex: Deserialize<'de>, M::Cost: Deserialize<'de>",
+)))]
 pub struct Movement<M: Map<U, T>, U: AgentIdxType, T> {
     node: M::Node,
     seats: Vec<(M::SeatIndex, Option<M::Cost>)>
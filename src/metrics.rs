@@ -0,0 +1,350 @@
+use std::{collections::BTreeMap, fmt::Display, hash::Hash, io::{self, Write}, ops::Sub, time::Duration};
+
+use num_traits::One;
+
+use crate::{agent_data::AgentState, index::index::Idx, map::Map, pathfind::common::Cost, seat::AgentIdxType, simulator::{Simulator, StepEvent}};
+
+// what an agent did from the end of the last recorded step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    NotPlaced,
+    // stopping with destinations left
+    Waiting,
+    Busy,
+    Idle,
+}
+
+#[derive(Debug, Clone)]
+pub struct AgentMetrics<C> {
+    added_at: C,
+    // when the agent stopped with no destinations left, reset if new destinations are given
+    finished_at: Option<C>,
+    removed_at: Option<C>,
+    n_reached: usize,
+    wait_time: C,
+    not_placed_time: C,
+    n_plans: usize,
+    n_failed_plans: usize,
+    planning_time: Duration,
+    phase: Phase,
+}
+
+impl<C: Cost + Sub<Output = C>> AgentMetrics<C> {
+    fn new(added_at: C, phase: Phase) -> Self {
+        Self {
+            added_at,
+            finished_at: None,
+            removed_at: None,
+            n_reached: 0,
+            wait_time: C::zero(),
+            not_placed_time: C::zero(),
+            n_plans: 0,
+            n_failed_plans: 0,
+            planning_time: Duration::ZERO,
+            phase,
+        }
+    }
+
+    pub fn added_at(&self) -> C { self.added_at }
+    pub fn finished_at(&self) -> Option<C> { self.finished_at }
+    pub fn removed_at(&self) -> Option<C> { self.removed_at }
+    pub fn n_reached(&self) -> usize { self.n_reached }
+    pub fn wait_time(&self) -> C { self.wait_time }
+    pub fn not_placed_time(&self) -> C { self.not_placed_time }
+    pub fn n_plans(&self) -> usize { self.n_plans }
+    pub fn n_failed_plans(&self) -> usize { self.n_failed_plans }
+    pub fn planning_time(&self) -> Duration { self.planning_time }
+
+    // time from the addition to the last destination
+    pub fn cost(&self) -> Option<C> { self.finished_at.map(|t| t - self.added_at) }
+
+    fn spend(&mut self, dt: C) {
+        match self.phase {
+            Phase::NotPlaced => self.not_placed_time = self.not_placed_time + dt,
+            Phase::Waiting => self.wait_time = self.wait_time + dt,
+            Phase::Busy | Phase::Idle => {},
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary<C> {
+    pub n_agents: usize,
+    pub n_finished: usize,
+    pub n_steps: usize,
+    pub sum_of_costs: C,
+    pub makespan: C,
+    // destinations reached per unit time
+    pub throughput: f64,
+    pub average_wait_time: f64,
+    pub not_placed_time: C,
+    pub n_plans: usize,
+    pub n_failed_plans: usize,
+    pub planning_time: Duration,
+}
+
+impl<C: Display> Summary<C> {
+    pub const CSV_HEADER: &'static str = "n_agents,n_finished,steps,sum_of_costs,makespan,throughput,average_wait_time,not_placed_time,plans,failed_plans,planning_time(msec)";
+
+    pub fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            self.n_agents, self.n_finished, self.n_steps, self.sum_of_costs, self.makespan, self.throughput,
+            self.average_wait_time, self.not_placed_time, self.n_plans, self.n_failed_plans, self.planning_time.as_secs_f64() * 1000.,
+        )
+    }
+}
+
+// collects the quality metrics of a run, `record` has to be called after every step
+pub struct Metrics<C, U: AgentIdxType, T = ()> {
+    start_time: C,
+    time: C,
+    n_steps: usize,
+    agents: BTreeMap<Idx<T, U>, AgentMetrics<C>>,
+}
+
+impl<C: Cost + Sub<Output = C>, U: AgentIdxType + Ord, T> Metrics<C, U, T> {
+    pub fn new<M: Map<U, T, Cost = C>>(s: &Simulator<M, U, T>) -> Self where M::SeatIndex: Hash {
+        let mut metrics = Self { start_time: s.time(), time: s.time(), n_steps: 0, agents: BTreeMap::new() };
+        metrics.observe(s, s.time());
+        metrics
+    }
+
+    pub fn time(&self) -> C { self.time }
+    pub fn n_steps(&self) -> usize { self.n_steps }
+    pub fn agents(&self) -> &BTreeMap<Idx<T, U>, AgentMetrics<C>> { &self.agents }
+    pub fn agent(&self, idx: Idx<T, U>) -> Option<&AgentMetrics<C>> { self.agents.get(&idx) }
+
+    pub fn record<M: Map<U, T, Cost = C>>(&mut self, s: &Simulator<M, U, T>) where M::SeatIndex: Hash, C: One {
+        let t = s.last_step_time();
+        self.n_steps += 1;
+
+        // ticks skipped before the step, in which nothing changed, so a waiting agent failed to plan at each of them as in the last step
+        if t > self.time {
+            let dt = t - self.time;
+            let mut n_ticks = 0;
+            let mut tick = self.time;
+            while tick < t {
+                tick = tick + C::one();
+                n_ticks += 1;
+            }
+            for a in self.agents.values_mut() {
+                a.spend(dt);
+                if a.phase == Phase::Waiting {
+                    a.n_plans += n_ticks;
+                    a.n_failed_plans += n_ticks;
+                }
+            }
+        }
+
+        for e in s.events() {
+            let Some(a) = self.agents.get_mut(&e.idx()) else { continue };
+            match *e {
+                StepEvent::Arrived { reached: true, .. } => a.n_reached += 1,
+                StepEvent::Planned { success, elapsed, .. } => {
+                    a.n_plans += 1;
+                    a.n_failed_plans += usize::from(!success);
+                    a.planning_time += elapsed;
+                },
+                StepEvent::Removed { .. } => {
+                    a.removed_at = Some(t);
+                    a.phase = Phase::Idle;
+                },
+                StepEvent::Placed(_) | StepEvent::Arrived { .. } => {},
+            }
+        }
+
+        self.observe(s, t);
+
+        let dt = s.time() - t;
+        self.agents.values_mut().for_each(|a| a.spend(dt));
+        self.time = s.time();
+    }
+
+    // `t` is the time at which agents seen for the first time are added
+    fn observe<M: Map<U, T, Cost = C>>(&mut self, s: &Simulator<M, U, T>, t: C) where M::SeatIndex: Hash {
+        for (&idx, a) in s.agents() {
+            let phase = match a.state() {
+                AgentState::NotPlaced => Phase::NotPlaced,
+                AgentState::Stop if a.all_destinations().is_empty() => Phase::Idle,
+                AgentState::Stop => Phase::Waiting,
+                AgentState::Moving { .. } => Phase::Busy,
+            };
+            let m = self.agents.entry(idx).or_insert_with(|| AgentMetrics::new(t, phase));
+            if phase == Phase::Idle {
+                m.finished_at.get_or_insert(t.max(m.added_at));
+            } else {
+                m.finished_at = None;
+            }
+            m.phase = phase;
+        }
+    }
+
+    pub fn summary(&self) -> Summary<C> where C: Into<f64> {
+        let costs = self.agents.values().filter_map(|a| a.cost());
+        let n_reached = self.agents.values().map(|a| a.n_reached).sum::<usize>();
+        let elapsed: f64 = (self.time - self.start_time).into();
+        let wait_time = self.agents.values().fold(C::zero(), |t, a| t + a.wait_time);
+
+        Summary {
+            n_agents: self.agents.len(),
+            n_finished: self.agents.values().filter(|a| a.finished_at.is_some()).count(),
+            n_steps: self.n_steps,
+            sum_of_costs: costs.fold(C::zero(), |c, a| c + a),
+            makespan: self.agents.values().filter_map(|a| a.finished_at).max().map_or(C::zero(), |t| t - self.start_time),
+            throughput: if elapsed > 0. { n_reached as f64 / elapsed } else { 0. },
+            average_wait_time: if self.agents.is_empty() { 0. } else { wait_time.into() / self.agents.len() as f64 },
+            not_placed_time: self.agents.values().fold(C::zero(), |t, a| t + a.not_placed_time),
+            n_plans: self.agents.values().map(|a| a.n_plans).sum(),
+            n_failed_plans: self.agents.values().map(|a| a.n_failed_plans).sum(),
+            planning_time: self.agents.values().map(|a| a.planning_time).sum(),
+        }
+    }
+
+    // one row per agent, empty columns for values which are not determined yet
+    pub fn write_csv<W: Write>(&self, mut w: W) -> io::Result<()> where C: Display, U: Display {
+        w.write_all(b"agent,added_at,finished_at,removed_at,cost,reached,wait_time,not_placed_time,plans,failed_plans,planning_time(msec)\n")?;
+        let optional = |c: Option<C>| c.map_or(String::new(), |c| c.to_string());
+        for (idx, a) in &self.agents {
+            writeln!(
+                w,
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                idx.value(), a.added_at, optional(a.finished_at), optional(a.removed_at), optional(a.cost()), a.n_reached,
+                a.wait_time, a.not_placed_time, a.n_plans, a.n_failed_plans, a.planning_time.as_secs_f64() * 1000.,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{graph::GraphMap, pathfind::common::MultipleEnds, simulator::{Simulator, StopReason}};
+
+    use super::Metrics;
+
+    fn line(n: usize) -> GraphMap {
+        let mut map = GraphMap::new();
+        for i in 0..n {
+            map.add_node(i as f32, 0.);
+        }
+        for i in 1..n {
+            map.add_edge(i - 1, i, 1);
+            map.add_edge(i, i - 1, 1);
+        }
+        map
+    }
+
+    #[test]
+    fn test() {
+        // 0 - 1 - 2 - 3, the second agent waits until the first one leaves node 1
+        let mut s = Simulator::new(0, line(4), 1);
+        let i0 = s.add((), 1, VecDeque::new()).unwrap();
+        let i1 = s.add((), 0, VecDeque::from([MultipleEnds::new_as_all_zero(vec![2])])).unwrap();
+
+        let mut metrics = Metrics::new(&s);
+        s.run_for(3, |s| metrics.record(s)).unwrap();
+        assert_eq!(metrics.agent(i0).unwrap().finished_at(), Some(0));
+        assert_eq!(metrics.agent(i1).unwrap().n_failed_plans(), 3);
+
+        s.agent_destination_mut(i0).unwrap().push_back(MultipleEnds::new_as_all_zero(vec![3]));
+        assert_eq!(s.run_until_idle_with(20, |s| metrics.record(s)).unwrap(), StopReason::Completed);
+
+        let (a0, a1) = (metrics.agent(i0).unwrap(), metrics.agent(i1).unwrap());
+        assert_eq!(a0.finished_at(), Some(5));
+        assert_eq!(a0.cost(), Some(5));
+        assert_eq!(a0.n_reached(), 1);
+        assert_eq!(a0.wait_time(), 0);
+        assert_eq!((a0.n_plans(), a0.n_failed_plans()), (2, 0));
+
+        assert_eq!(a1.finished_at(), Some(6));
+        assert_eq!(a1.n_reached(), 1);
+        // stops at node 0 from 0 to 4
+        assert_eq!(a1.wait_time(), 4);
+        assert_eq!((a1.n_plans(), a1.n_failed_plans()), (6, 4));
+
+        let summary = metrics.summary();
+        assert_eq!(summary.n_agents, 2);
+        assert_eq!(summary.n_finished, 2);
+        assert_eq!(summary.n_steps, 7);
+        assert_eq!(summary.sum_of_costs, 11);
+        assert_eq!(summary.makespan, 6);
+        assert_eq!(summary.average_wait_time, 2.);
+        assert_eq!(summary.throughput, 2. / 7.);
+        assert_eq!((summary.n_plans, summary.n_failed_plans), (8, 4));
+        assert_eq!(summary.planning_time, a0.planning_time() + a1.planning_time());
+    }
+
+    #[test]
+    fn skipped_ticks_test() {
+        // 0 - 1 - 2 and 3 - 4 which takes 6 ticks
+        // the second agent cannot pass the first one parked at node 1 while the third one is moving to node 4
+        let mut map = line(3);
+        map.add_node(3., 0.);
+        map.add_node(4., 0.);
+        map.add_edge(3, 4, 6);
+        let run = |skip: bool| {
+            let mut s = Simulator::new(0, map.clone(), 10);
+            s.add((), 1, VecDeque::new()).unwrap();
+            let i1 = s.add((), 0, VecDeque::from([MultipleEnds::new_as_all_zero(vec![2])])).unwrap();
+            s.add((), 3, VecDeque::from([MultipleEnds::new_as_all_zero(vec![4])])).unwrap();
+            let mut metrics = Metrics::new(&s);
+            while s.time() < 8 {
+                if skip { s.step_to_next_event().unwrap() } else { s.step().unwrap() }
+                metrics.record(&s);
+            }
+            let a1 = metrics.agent(i1).unwrap();
+            (metrics.n_steps(), a1.n_plans(), a1.n_failed_plans(), a1.wait_time())
+        };
+
+        let (n_steps, n_plans, n_failed, wait_time) = run(false);
+        assert_eq!((n_steps, n_plans, n_failed, wait_time), (8, 8, 8, 8));
+        // the ticks skipped while nothing changes count as the failed plans repeated in them
+        let (n_steps, n_plans, n_failed, wait_time) = run(true);
+        assert!(n_steps < 8);
+        assert_eq!((n_plans, n_failed, wait_time), (8, 8, 8));
+    }
+
+    #[test]
+    fn not_placed_test() {
+        // the second agent is added on the seat of the first one
+        let mut s = Simulator::new(0, line(3), 1);
+        let i0 = s.add((), 0, VecDeque::new()).unwrap();
+        let mut metrics = Metrics::new(&s);
+        s.step().unwrap();
+        metrics.record(&s);
+
+        let i1 = s.add((), 0, VecDeque::new()).unwrap();
+        s.run_for(2, |s| metrics.record(s)).unwrap();
+        s.agent_destination_mut(i0).unwrap().push_back(MultipleEnds::new_as_all_zero(vec![2]));
+        assert_eq!(s.run_until_idle_with(20, |s| metrics.record(s)).unwrap(), StopReason::Completed);
+
+        // placed when the first agent arrives at node 1 at 4
+        let a1 = metrics.agent(i1).unwrap();
+        assert_eq!(a1.added_at(), 1);
+        assert_eq!(a1.not_placed_time(), 3);
+        assert_eq!(a1.n_plans(), 0);
+        assert_eq!(a1.finished_at(), Some(4));
+        assert_eq!(metrics.summary().not_placed_time, 3);
+    }
+
+    #[test]
+    fn csv_test() {
+        let mut s = Simulator::new(0, line(2), 1);
+        s.add((), 0, VecDeque::from([MultipleEnds::new_as_all_zero(vec![1])])).unwrap();
+        let mut metrics = Metrics::new(&s);
+        s.run_until_idle_with(20, |s| metrics.record(s)).unwrap();
+
+        let mut csv = vec![];
+        metrics.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("0,0,1,,1,1,0,0,1,0,"));
+
+        let summary = metrics.summary();
+        assert_eq!(summary.csv_row().split(',').count(), super::Summary::<u32>::CSV_HEADER.split(',').count());
+    }
+}
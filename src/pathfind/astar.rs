@@ -44,7 +44,7 @@ pub fn astar_for_next_reservation<N, C, S, FN, IN, IS, FS, FH, T>(
         |c| RCost::Add { dc: c, max: max_reservation_cost },
         |n| RCost::Add { dc: heuristic(n), max: max_reservation_cost }
     )
-        .and_then(|path| Some(collect_path_for_reservation(path)))
+        .map(collect_path_for_reservation)
 }
 
 pub fn astar_for_multiple_ends<N, C, MC, FN, IN, T, FC, FH>(
@@ -66,15 +66,15 @@ pub fn astar_for_multiple_ends<N, C, MC, FN, IN, T, FC, FH>(
 {
     if ends.is_empty() { return None }
 
-    let successors = |n: &NodeCost<NodeDest<N>, C, T>| {
+    let mut successors = |n: &NodeCost<NodeDest<N>, C, T>| {
         let (node, c0) = (n.node(), n.cost());
         match node {
             NodeDest::Node(n) => successors(n)
                 .into_iter()
                 .map(move |(m, c, t)| (NodeCost::new(NodeDest::Node(m), c0 + c, t), c))
                 .chain(
-                    ends.end_index(&n)
-                        .and_then(|i| Some(vec![(NodeCost::new(NodeDest::Dest, C::zero(), T::default()), converter(i))]))
+                    ends.end_index(n)
+                        .map(|i| vec![(NodeCost::new(NodeDest::Dest, C::zero(), T::default()), converter(i))])
                         .unwrap_or_default()
                 ),
             NodeDest::Dest => panic!(),
@@ -89,8 +89,8 @@ pub fn astar_for_multiple_ends<N, C, MC, FN, IN, T, FC, FH>(
         }
     };
 
-    astar(&NodeCost::new(NodeDest::Node(start.clone()), C::zero(), T::default()), successors, heuristic, |n| n.node() == &NodeDest::Dest)
-        .and_then(|(path, _)| Some(collect_path(path)))
+    astar(&NodeCost::new(NodeDest::Node(start.clone()), C::zero(), T::default()), &mut successors, heuristic, |n| n.node() == &NodeDest::Dest)
+        .map(|(path, _)| collect_path(path, successors))
 }
 
 #[cfg(test)]
@@ -122,6 +122,18 @@ mod tests {
         assert_eq!(path.iter().map(|(_, c, _)| *c).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
     }
 
+    #[test]
+    fn cheaper_route_found_later_test() {
+        // 2 is found first through the edge 1 of 0, then more cheaply through the edge 0 of 1
+        let edges = [vec![(1, 1), (2, 3)], vec![(2, 1)], vec![]];
+        let ends = MultipleEnds::new_as_all_zero(vec![2]);
+        let successors = |&n: &usize| edges[n].iter().enumerate().map(|(i, &(m, c))| (m, c, i)).collect::<Vec<_>>();
+
+        let path = astar_for_multiple_ends(&0, &ends, successors, |c| c, |_| 0).unwrap();
+
+        assert_eq!(path.iter().cloned().collect::<Vec<_>>(), vec![(1, 1, 0), (2, 2, 0)]);
+    }
+
     #[test]
     fn multiple_ends_test2() {
         let ends = MultipleEnds::new(HashMap::from([((6, -1), 0), ((5, 1), 1000)]));
@@ -199,14 +211,14 @@ mod tests {
 
         let successors = |&(x, y): &_| vec![(x + 1, y), (x, y + 1), (x - 1, y), (x, y - 1)]
             .into_iter()
-            .filter_map(|(x, y): (i32, i32)| if 0 <= x && x < 10 && 0 <= y && y < 10 {
+            .filter_map(|(x, y): (i32, i32)| if (0..10).contains(&x) && (0..10).contains(&y) {
                 Some(((x, y), 1, s3x3(x, y), ())) } else { None }
             );
 
         let seats_reservation = |&(x, y): &_| {
             match x {
                 ..=2 => y < 4,
-                ..=5 => y < 4 || y >= 6,
+                3..=5 => !(4..6).contains(&y),
                 _ => y >= 6,
             }
         };
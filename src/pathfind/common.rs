@@ -1,6 +1,8 @@
 use std::{collections::HashMap, hash::{Hash, Hasher}, ops::{Add, Index}, slice::Iter, vec::IntoIter};
 
 use num_traits::Zero;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use trait_set::trait_set;
 
 trait_set! {
@@ -9,6 +11,7 @@ trait_set! {
     pub trait Seat = Eq + Clone;
 }
 
+#[derive(Debug, Clone)]
 pub struct MultipleEnds<N: Node, C: Cost> {
     ends: HashMap<N, C>,
 }
@@ -26,7 +29,23 @@ impl<N: Node, C: Cost> MultipleEnds<N, C> {
     pub fn ends(&self) -> &HashMap<N, C> { &self.ends }
 }
 
+// serialized as a sequence of (node, cost) so that any node type can be used as a key
+#[cfg(feature = "serde")]
+impl<N: Node + Serialize, C: Cost + Serialize> Serialize for MultipleEnds<N, C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.ends.iter())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, N: Node + Deserialize<'de>, C: Cost + Deserialize<'de>> Deserialize<'de> for MultipleEnds<N, C> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<(N, C)>::deserialize(deserializer).map(|ends| Self::new(ends.into_iter().collect()))
+    }
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Path<N: Node, C: Cost, T = ()> {
     nodes: Vec<(N, C, T)>,
 }
@@ -39,8 +58,14 @@ impl<N: Node, C: Cost, T> Path<N, C, T> {
     pub fn node(&self, index: usize) -> &(N, C, T) { &self.nodes[index] }
     pub fn total_cost(&self) -> C { self.nodes[self.nodes.len() - 1].1 }
     pub fn len(&self) -> usize { self.nodes.len() }
+    pub fn is_empty(&self) -> bool { self.nodes.is_empty() }
     pub fn iter(&self) -> Iter<'_, (N, C, T)> { self.nodes.iter() }
-    pub fn into_iter(self) -> IntoIter<(N, C, T)> { self.nodes.into_iter() }
+}
+
+impl<N: Node, C: Cost, T> IntoIterator for Path<N, C, T> {
+    type Item = (N, C, T);
+    type IntoIter = IntoIter<(N, C, T)>;
+    fn into_iter(self) -> Self::IntoIter { self.nodes.into_iter() }
 }
 
 impl<N: Node, C: Cost, T> Index<usize> for Path<N, C, T> {
@@ -80,6 +105,7 @@ impl<N: Node, C: Cost, T: Clone> Hash for NodeCost<N, C, T> {
 pub(crate) enum NodeDest<N: Node> { Node(N), Dest }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum RCost<C: Cost> {
     Cost { cost: C, r: C, blocked: bool },
     Add { dc: C, max: C },
@@ -124,9 +150,28 @@ impl<C: Cost> Add for RCost<C> {
     }
 }
 
-pub(crate) fn collect_path<N: Node, C: Cost, T: Clone>(path: Vec<NodeCost<NodeDest<N>, C, T>>) -> Path<N, C, T> {
-    Path::new(path[1..]
-        .into_iter()
+// pathfinding keeps the node first found as the key even if a cheaper route to it is found later,
+// so the cost and the attribute of each node are taken again from its actual predecessor
+pub(crate) fn collect_path<N: Node, C: Cost, T: Clone, FN, IN>(path: Vec<NodeCost<NodeDest<N>, C, T>>, mut successors: FN) -> Path<N, C, T> where
+    FN: FnMut(&NodeCost<NodeDest<N>, C, T>) -> IN,
+    IN: IntoIterator<Item = (NodeCost<NodeDest<N>, C, T>, C)>,
+{
+    let mut nodes: Vec<NodeCost<NodeDest<N>, C, T>> = Vec::with_capacity(path.len());
+    for n in path {
+        let n = match nodes.last() {
+            Some(prev) => successors(prev)
+                .into_iter()
+                .map(|(m, _)| m)
+                .filter(|m| m == &n)
+                .min_by_key(|m| m.cost())
+                .unwrap_or(n),
+            None => n,
+        };
+        nodes.push(n);
+    }
+
+    Path::new(nodes[1..]
+        .iter()
         .filter_map(|node|
             match node.node() {
                 NodeDest::Node(n) => Some((n.clone(), node.cost(), node.attr().clone())),
@@ -156,4 +201,23 @@ pub(crate) fn collect_path_for_reservation<N: Node, C: Cost, T: Clone>(path: Pat
             })
             .collect::<Vec<(N, C, T)>>()
     )
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_test() {
+        use std::collections::HashMap;
+
+        use super::{MultipleEnds, Path};
+
+        let ends = MultipleEnds::new(HashMap::from([((1, 2), 0), ((3, 4), 5)]));
+        let json = serde_json::to_string(&ends).unwrap();
+        assert_eq!(serde_json::from_str::<MultipleEnds<(i32, i32), u32>>(&json).unwrap().ends(), ends.ends());
+
+        let path = Path::new(vec![((1, 2), 1, ()), ((1, 3), 2, ())]);
+        let json = serde_json::to_string(&path).unwrap();
+        let path2 = serde_json::from_str::<Path<(i32, i32), u32>>(&json).unwrap();
+        assert_eq!(path.iter().collect::<Vec<_>>(), path2.iter().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,346 @@
+use std::{collections::VecDeque, fmt::{Debug, Display, Formatter}, hash::{Hash, Hasher}, io::{Read, Write}};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{agent_data::{AgentState, RemovalMode}, index::{allocator::Allocator, index::Idx}, map::Map, pathfind::common::{Cost, MultipleEnds, Node}, seat::{AgentIdxType, Seat}, simulator::{Clock, Planner, Simulator}, Error};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Input<N: Node, C: Cost, T, U: AgentIdxType> {
+    Add { kind: T, node: N, destinations: VecDeque<MultipleEnds<N, C>>, idx: U },
+    // recordings without the mode are graceful removals
+    Remove {
+        idx: U,
+        #[serde(default)]
+        mode: RemovalMode,
+    },
+    CancelRemoval { idx: U },
+    SetDestinations { idx: U, destinations: VecDeque<MultipleEnds<N, C>> },
+    // the index was chosen by the caller
+    AddWithIdx { kind: T, node: N, destinations: VecDeque<MultipleEnds<N, C>>, idx: U },
+    CancelPath { idx: U },
+    Replan { idx: U },
+    SetPlanner { planner: Planner },
+    // the allocator with its state at the time, a custom one is written as a monotonic one
+    SetAllocator { allocator: Allocator<U> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording<N: Node, C: Cost, T, U: AgentIdxType> {
+    init_time: C,
+    max_reservation_time: C,
+    clock: Clock,
+    // (number of steps done before the input, input)
+    inputs: Vec<(usize, Input<N, C, T, U>)>,
+    // digest of the state after each step
+    states: Vec<u64>,
+}
+
+impl<N: Node + Serialize + DeserializeOwned, C: Cost + Serialize + DeserializeOwned, T: Serialize + DeserializeOwned, U: AgentIdxType + Serialize + DeserializeOwned> Recording<N, C, T, U> {
+    pub fn write<W: Write>(&self, writer: W) -> serde_json::Result<()> {
+        serde_json::to_writer(writer, self)
+    }
+
+    pub fn read<R: Read>(reader: R) -> serde_json::Result<Self> {
+        serde_json::from_reader(reader)
+    }
+}
+
+impl<N: Node, C: Cost, T, U: AgentIdxType> Recording<N, C, T, U> {
+    #[allow(clippy::type_complexity)]
+    pub fn inputs(&self) -> &Vec<(usize, Input<N, C, T, U>)> { &self.inputs }
+    pub fn n_steps(&self) -> usize { self.states.len() }
+}
+
+pub struct Recorder<M: Map<U, T>, U: AgentIdxType + Ord, T = ()> {
+    simulator: Simulator<M, U, T>,
+    recording: Recording<M::Node, M::Cost, T, U>,
+}
+
+#[allow(clippy::type_complexity)]
+impl<M: Map<U, T>, U: AgentIdxType + Ord + Hash, T: Clone> Recorder<M, U, T> where M::SeatIndex: Hash {
+    pub fn new(init_time: M::Cost, map: M, max_reservation_time: M::Cost, clock: Clock) -> Self {
+        Self {
+            simulator: Simulator::new_with_clock(init_time, map, max_reservation_time, clock),
+            recording: Recording { init_time, max_reservation_time, clock, inputs: vec![], states: vec![] },
+        }
+    }
+
+    pub fn simulator(&self) -> &Simulator<M, U, T> { &self.simulator }
+    pub fn recording(&self) -> &Recording<M::Node, M::Cost, T, U> { &self.recording }
+
+    pub fn finish(self) -> (Simulator<M, U, T>, Recording<M::Node, M::Cost, T, U>) {
+        (self.simulator, self.recording)
+    }
+
+    // rejected inputs are not recorded
+    pub fn add(&mut self, kind: T, node: M::Node, destinations: VecDeque<MultipleEnds<M::Node, M::Cost>>) -> Result<Idx<T, U>, Error<M::Node, M::SeatIndex, T, U>> {
+        let idx = self.simulator.add(kind.clone(), node.clone(), destinations.clone())?;
+        self.push(Input::Add { kind, node, destinations, idx: idx.value() });
+        Ok(idx)
+    }
+
+    pub fn remove(&mut self, idx: Idx<T, U>) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        self.remove_with(idx, RemovalMode::Graceful)
+    }
+
+    pub fn remove_with(&mut self, idx: Idx<T, U>, mode: RemovalMode) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        self.simulator.remove_with(idx, mode)?;
+        self.push(Input::Remove { idx: idx.value(), mode });
+        Ok(())
+    }
+
+    pub fn cancel_removal(&mut self, idx: Idx<T, U>) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        self.simulator.cancel_removal(idx)?;
+        self.push(Input::CancelRemoval { idx: idx.value() });
+        Ok(())
+    }
+
+    pub fn edit_destinations<F: FnOnce(&mut VecDeque<MultipleEnds<M::Node, M::Cost>>)>(&mut self, idx: Idx<T, U>, f: F) -> bool {
+        let Some(destinations) = self.simulator.agent_destination_mut(idx) else { return false };
+        f(destinations);
+        let destinations = destinations.clone();
+        self.push(Input::SetDestinations { idx: idx.value(), destinations });
+        true
+    }
+
+    pub fn add_with_idx(&mut self, idx: Idx<T, U>, kind: T, node: M::Node, destinations: VecDeque<MultipleEnds<M::Node, M::Cost>>) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        self.simulator.add_with_idx(idx, kind.clone(), node.clone(), destinations.clone())?;
+        self.push(Input::AddWithIdx { kind, node, destinations, idx: idx.value() });
+        Ok(())
+    }
+
+    pub fn cancel_path(&mut self, idx: Idx<T, U>) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        self.simulator.cancel_path(idx)?;
+        self.push(Input::CancelPath { idx: idx.value() });
+        Ok(())
+    }
+
+    pub fn replan(&mut self, idx: Idx<T, U>) -> Result<bool, Error<M::Node, M::SeatIndex, T, U>> {
+        let planned = self.simulator.replan(idx)?;
+        self.push(Input::Replan { idx: idx.value() });
+        Ok(planned)
+    }
+
+    pub fn set_planner(&mut self, planner: Planner) {
+        self.simulator.set_planner(planner);
+        self.push(Input::SetPlanner { planner });
+    }
+
+    pub fn set_allocator<A: Into<Allocator<U>>>(&mut self, allocator: A) {
+        let allocator = allocator.into();
+        self.simulator.set_allocator(allocator.clone());
+        self.push(Input::SetAllocator { allocator });
+    }
+
+    pub fn step(&mut self) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        self.simulator.step()?;
+        self.recording.states.push(digest(&self.simulator));
+        Ok(())
+    }
+
+    fn push(&mut self, input: Input<M::Node, M::Cost, T, U>) {
+        self.recording.inputs.push((self.recording.states.len(), input));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError<U> {
+    // the simulator assigned another index than the recorded one
+    IdxMismatch { step: usize, expected: U, actual: U },
+    UnknownAgent { step: usize, idx: U },
+    StateMismatch { step: usize },
+    // the simulator rejected an input or failed in the step
+    Simulator { step: usize, message: String },
+}
+
+impl<U: Display> Display for ReplayError<U> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IdxMismatch { step, expected, actual } => write!(f, "agent {} was added as {} at step {}", expected, actual, step),
+            Self::UnknownAgent { step, idx } => write!(f, "agent {} does not exist at step {}", idx, step),
+            Self::StateMismatch { step } => write!(f, "state differs from the recording after step {}", step),
+            Self::Simulator { step, message } => write!(f, "simulator failed at step {}: {}", step, message),
+        }
+    }
+}
+
+impl<U: Display + Debug> std::error::Error for ReplayError<U> {}
+
+pub struct Replayer<M: Map<U, T>, U: AgentIdxType + Ord, T = ()> {
+    simulator: Simulator<M, U, T>,
+    recording: Recording<M::Node, M::Cost, T, U>,
+    next_input: usize,
+    n_steps: usize,
+    assertion: bool,
+}
+
+impl<M: Map<U, T>, U: AgentIdxType + Ord + Hash, T: Clone> Replayer<M, U, T> where M::SeatIndex: Hash {
+    pub fn new(recording: Recording<M::Node, M::Cost, T, U>, map: M) -> Self {
+        let simulator = Simulator::new_with_clock(recording.init_time, map, recording.max_reservation_time, recording.clock);
+        Self { simulator, recording, next_input: 0, n_steps: 0, assertion: false }
+    }
+
+    // checks the state after every step against the recording
+    pub fn new_with_assertion(recording: Recording<M::Node, M::Cost, T, U>, map: M) -> Self {
+        Self { assertion: true, ..Self::new(recording, map) }
+    }
+
+    pub fn simulator(&self) -> &Simulator<M, U, T> { &self.simulator }
+    pub fn n_steps(&self) -> usize { self.n_steps }
+    pub fn is_finished(&self) -> bool { self.n_steps >= self.recording.n_steps() }
+
+    // applies the inputs recorded before the next step and steps, returns false if the recording is over
+    pub fn step(&mut self) -> Result<bool, ReplayError<U>> where M::Node: Debug, M::SeatIndex: Debug, U: Debug {
+        if self.is_finished() {
+            return Ok(false)
+        }
+        self.apply_inputs()?;
+        let step = self.n_steps;
+        self.simulator.step().map_err(|e| ReplayError::Simulator { step, message: e.to_string() })?;
+
+        if self.assertion && digest(&self.simulator) != self.recording.states[self.n_steps] {
+            return Err(ReplayError::StateMismatch { step: self.n_steps })
+        }
+        self.n_steps += 1;
+        Ok(true)
+    }
+
+    pub fn run(&mut self) -> Result<(), ReplayError<U>> where M::Node: Debug, M::SeatIndex: Debug, U: Debug {
+        while self.step()? {}
+        self.apply_inputs()
+    }
+
+    fn apply_inputs(&mut self) -> Result<(), ReplayError<U>> where M::Node: Debug, M::SeatIndex: Debug, U: Debug {
+        let step = self.n_steps;
+        let error = |e: Error<M::Node, M::SeatIndex, T, U>| match e {
+            Error::UnknownAgent(idx) => ReplayError::UnknownAgent { step, idx: idx.value() },
+            e => ReplayError::Simulator { step, message: e.to_string() },
+        };
+        while let Some((n, input)) = self.recording.inputs.get(self.next_input) {
+            if *n > step {
+                break;
+            }
+            match input {
+                Input::Add { kind, node, destinations, idx } => {
+                    let actual = self.simulator.add(kind.clone(), node.clone(), destinations.clone())
+                        .map_err(|e| ReplayError::Simulator { step, message: e.to_string() })?
+                        .value();
+                    if actual != *idx {
+                        return Err(ReplayError::IdxMismatch { step, expected: *idx, actual })
+                    }
+                },
+                Input::Remove { idx, mode } => self.simulator.remove_with(Idx::new(*idx), *mode).map_err(error)?,
+                Input::CancelRemoval { idx } => self.simulator.cancel_removal(Idx::new(*idx)).map_err(error)?,
+                Input::SetDestinations { idx, destinations } => {
+                    let Some(d) = self.simulator.agent_destination_mut(Idx::new(*idx)) else {
+                        return Err(ReplayError::UnknownAgent { step, idx: *idx })
+                    };
+                    *d = destinations.clone();
+                },
+                Input::AddWithIdx { kind, node, destinations, idx } => {
+                    self.simulator.add_with_idx(Idx::new(*idx), kind.clone(), node.clone(), destinations.clone()).map_err(error)?;
+                },
+                Input::CancelPath { idx } => self.simulator.cancel_path(Idx::new(*idx)).map_err(error)?,
+                Input::Replan { idx } => { self.simulator.replan(Idx::new(*idx)).map_err(error)?; },
+                Input::SetPlanner { planner } => self.simulator.set_planner(*planner),
+                Input::SetAllocator { allocator } => self.simulator.set_allocator(allocator.clone()),
+            }
+            self.next_input += 1;
+        }
+        Ok(())
+    }
+}
+
+// recordings keep digests, so they are written in a fixed-width little-endian encoding and hashed with FNV-1a,
+// which are the same on every platform and Rust version unlike DefaultHasher
+fn digest<M: Map<U, T>, U: AgentIdxType + Ord + Hash, T>(s: &Simulator<M, U, T>) -> u64 where M::SeatIndex: Hash {
+    let mut h = StableHasher::new();
+    s.time().hash(&mut h);
+    for (&idx, a) in s.agents() {
+        idx.hash(&mut h);
+        a.current().hash(&mut h);
+        match a.state() {
+            AgentState::NotPlaced => h.write_u8(0),
+            AgentState::Stop => h.write_u8(1),
+            AgentState::Moving { nexts } => {
+                h.write_u8(2);
+                nexts.hash(&mut h);
+            },
+        }
+        h.write_u64(a.all_destinations().len() as u64);
+        a.removal().hash(&mut h);
+
+        // reserved seats are in no particular order
+        let reserved = s.reserved_seats(idx).unwrap_or_default();
+        h.write_u64(reserved.len() as u64);
+        h.write_u64(reserved.iter()
+            .map(|(seat, t)| {
+                let mut h = StableHasher::new();
+                seat.hash(&mut h);
+                t.hash(&mut h);
+                s.map()[seat.clone()].is_held_by(idx).hash(&mut h);
+                h.finish()
+            })
+            .fold(0, u64::wrapping_add));
+        for seat in s.map().seats(a.current(), a.kind()) {
+            s.map()[seat].is_held_by(idx).hash(&mut h);
+        }
+    }
+    h.finish()
+}
+
+struct StableHasher(u64);
+
+impl StableHasher {
+    fn new() -> Self { Self(0xcbf29ce484222325) }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 { self.0 }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) { self.write(&i.to_le_bytes()) }
+    fn write_u32(&mut self, i: u32) { self.write(&i.to_le_bytes()) }
+    fn write_u64(&mut self, i: u64) { self.write(&i.to_le_bytes()) }
+    fn write_u128(&mut self, i: u128) { self.write(&i.to_le_bytes()) }
+    fn write_usize(&mut self, i: usize) { self.write_u64(i as u64) }
+    fn write_isize(&mut self, i: isize) { self.write_u64(i as i64 as u64) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, hash::{Hash, Hasher}};
+
+    use crate::{grid::map::GridMap, pathfind::common::MultipleEnds, simulator::Simulator};
+
+    use super::{digest, StableHasher};
+
+    #[test]
+    fn stable_hasher_test() {
+        let hash = |v: &dyn Fn(&mut StableHasher)| {
+            let mut h = StableHasher::new();
+            v(&mut h);
+            h.finish()
+        };
+        assert_eq!(hash(&|_| {}), 0xcbf29ce484222325);
+        assert_eq!(hash(&|h| h.write(b"a")), 0xaf63dc4c8601ec8c);
+        assert_eq!(hash(&|h| 1usize.hash(h)), hash(&|h| 1u64.hash(h)));
+        assert_eq!(hash(&|h| (-1isize).hash(h)), hash(&|h| (-1i64).hash(h)));
+        assert_eq!(hash(&|h| 0x0102u16.hash(h)), hash(&|h| h.write(&[2, 1])));
+    }
+
+    #[test]
+    fn digest_test() {
+        let mut s: Simulator<GridMap, u32> = Simulator::new(0, GridMap::new(3, 2), 2);
+        s.add((), (0, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(2, 0)])])).unwrap();
+        s.add((), (0, 1), VecDeque::new()).unwrap();
+        s.step().unwrap();
+        // recorded digests must not change with the platform or the Rust version
+        assert_eq!(digest(&s), 0xb235801748eae867);
+    }
+}
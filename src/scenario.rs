@@ -0,0 +1,464 @@
+use std::{collections::{HashMap, HashSet, VecDeque}, fmt::{Debug, Display, Formatter}, fs, hash::Hash, path::{Path, PathBuf}};
+
+use num_traits::Zero;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{index::index::Idx, map::Map, pathfind::common::{Cost, MultipleEnds, Node}, seat::AgentIdxType, simulator::{Clock, Planner, Simulator, StopReason}, Error};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MapFormat {
+    Ascii,
+    MovingAi,
+    Graph,
+}
+
+// the map is not loaded here, it only tells the caller what to pass to `build`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapRef {
+    pub format: MapFormat,
+    // relative to the scenario file
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(deserialize = "C: Deserialize<'de> + Zero"))]
+pub struct Settings<C> {
+    #[serde(default = "C::zero")]
+    pub init_time: C,
+    pub max_reservation_time: C,
+    #[serde(default)]
+    pub clock: Clock,
+    #[serde(default)]
+    pub planner: Planner,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(deserialize = "N: Deserialize<'de>, C: Deserialize<'de> + Zero, T: Deserialize<'de> + Default"))]
+pub struct Scenario<N, C, T = ()> {
+    #[serde(default)]
+    pub map: Option<MapRef>,
+    pub settings: Settings<C>,
+    #[serde(default = "Vec::new")]
+    pub agents: Vec<AgentSpec<N, C, T>>,
+    #[serde(default = "Vec::new")]
+    pub events: Vec<Event<N, C, T>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(deserialize = "N: Deserialize<'de>, C: Deserialize<'de>, T: Deserialize<'de> + Default"))]
+pub struct AgentSpec<N, C, T = ()> {
+    pub id: String,
+    #[serde(default)]
+    pub kind: T,
+    pub start: N,
+    #[serde(default = "Vec::new")]
+    pub destinations: Vec<DestinationSpec<N, C>>,
+}
+
+// one element of the destination queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DestinationSpec<N, C> {
+    // any of them, without weights
+    Nodes(Vec<N>),
+    Weighted(Vec<WeightedEnd<N, C>>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeightedEnd<N, C> {
+    pub node: N,
+    pub weight: C,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(deserialize = "N: Deserialize<'de>, C: Deserialize<'de>, T: Deserialize<'de> + Default"))]
+pub struct Event<N, C, T = ()> {
+    pub at: C,
+    #[serde(flatten)]
+    pub action: Action<N, C, T>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(bound(deserialize = "N: Deserialize<'de>, C: Deserialize<'de>, T: Deserialize<'de> + Default"))]
+pub enum Action<N, C, T = ()> {
+    Add(AgentSpec<N, C, T>),
+    Remove(String),
+    SetDestinations { id: String, destinations: Vec<DestinationSpec<N, C>> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScenarioError {
+    Io(String),
+    Parse(String),
+    // `entry` is the path to the offending entry such as "agents[2].start"
+    Invalid { entry: String, message: String },
+    // the simulator failed in a step
+    Simulator(String),
+}
+
+impl ScenarioError {
+    fn invalid<E: Display, S: Display>(entry: E, message: S) -> Self {
+        Self::Invalid { entry: entry.to_string(), message: message.to_string() }
+    }
+}
+
+impl Display for ScenarioError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Parse(e) => write!(f, "{}", e),
+            Self::Invalid { entry, message } => write!(f, "{}: {}", entry, message),
+            Self::Simulator(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+#[derive(Deserialize)]
+struct Header {
+    #[serde(default)]
+    map: Option<MapRef>,
+}
+
+fn read(path: &Path) -> Result<String, ScenarioError> {
+    fs::read_to_string(path).map_err(|e| ScenarioError::Io(format!("{}: {}", path.display(), e)))
+}
+
+fn parse<D: DeserializeOwned>(path: &Path, text: &str) -> Result<D, ScenarioError> {
+    let error = |e: &dyn Display| ScenarioError::Parse(format!("{}: {}", path.display(), e));
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("toml") => toml::from_str(text).map_err(|e| error(&e)),
+        Some("json") => serde_json::from_str(text).map_err(|e| error(&e)),
+        _ => Err(error(&"the extension must be .toml or .json")),
+    }
+}
+
+// reads only the map reference, to decide the node type before loading the whole scenario
+pub fn read_map_ref(path: &Path) -> Result<Option<MapRef>, ScenarioError> {
+    parse::<Header>(path, &read(path)?).map(|h| h.map)
+}
+
+impl<N: DeserializeOwned, C: Zero + DeserializeOwned, T: Default + DeserializeOwned> Scenario<N, C, T> {
+    pub fn from_toml(text: &str) -> Result<Self, ScenarioError> {
+        toml::from_str(text).map_err(|e| ScenarioError::Parse(e.to_string()))
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, ScenarioError> {
+        serde_json::from_str(text).map_err(|e| ScenarioError::Parse(e.to_string()))
+    }
+
+    // the format is chosen by the extension
+    pub fn load(path: &Path) -> Result<Self, ScenarioError> {
+        parse(path, &read(path)?)
+    }
+}
+
+impl<N: Node, C: Cost, T: Clone> Scenario<N, C, T> {
+    pub fn validate<M: Map<U, T, Node = N, Cost = C>, U: AgentIdxType>(&self, map: &M) -> Result<(), ScenarioError> {
+        let mut known = HashSet::new();
+        let mut alive = HashSet::new();
+
+        for (k, a) in self.agents.iter().enumerate() {
+            validate_agent(map, a, &format!("agents[{}]", k))?;
+            if !known.insert(a.id.as_str()) {
+                return Err(ScenarioError::invalid(format!("agents[{}].id", k), format!("duplicate id '{}'", a.id)))
+            }
+            alive.insert(a.id.as_str());
+        }
+
+        for (k, e) in self.sorted_events() {
+            match &e.action {
+                Action::Add(a) => {
+                    validate_agent(map, a, &format!("events[{}].add", k))?;
+                    if !known.insert(a.id.as_str()) {
+                        return Err(ScenarioError::invalid(format!("events[{}].add.id", k), format!("duplicate id '{}'", a.id)))
+                    }
+                    alive.insert(a.id.as_str());
+                },
+                Action::Remove(id) => {
+                    if !alive.remove(id.as_str()) {
+                        return Err(ScenarioError::invalid(format!("events[{}].remove", k), format!("agent '{}' does not exist at this time", id)))
+                    }
+                },
+                Action::SetDestinations { id, destinations } => {
+                    let entry = format!("events[{}].set_destinations", k);
+                    if !alive.contains(id.as_str()) {
+                        return Err(ScenarioError::invalid(format!("{}.id", entry), format!("agent '{}' does not exist at this time", id)))
+                    }
+                    let kind = self.kind_of(id).expect("known ids have a kind");
+                    validate_destinations(map, destinations, kind, &format!("{}.destinations", entry))?;
+                },
+            }
+        }
+        Ok(())
+    }
+
+    // validates the scenario and adds the initial agents, the events are returned as a schedule
+    #[allow(clippy::type_complexity)]
+    pub fn build<M: Map<U, T, Node = N, Cost = C>, U: AgentIdxType + Ord + Hash + Debug>(self, map: M) -> Result<(Simulator<M, U, T>, Schedule<N, C, T, U>), ScenarioError> where N: Debug, M::SeatIndex: Hash + Debug {
+        self.validate(&map)?;
+
+        let mut s = Simulator::new_with_clock(self.settings.init_time, map, self.settings.max_reservation_time, self.settings.clock);
+        s.set_planner(self.settings.planner);
+
+        let mut ids = HashMap::new();
+        for (k, a) in self.agents.into_iter().enumerate() {
+            // destinations which cannot be reached are found only here
+            let idx = s.add(a.kind, a.start, destinations(a.destinations)).map_err(|e| ScenarioError::invalid(format!("agents[{}]", k), e))?;
+            ids.insert(a.id, idx);
+        }
+
+        let mut events = self.events.into_iter().enumerate().collect::<Vec<_>>();
+        events.sort_by_key(|(k, e)| (e.at, *k));
+        let events = events.into_iter()
+            .map(|(k, e)| {
+                let action = match e.action {
+                    Action::Add(a) => ScheduledAction::Add { id: a.id, kind: a.kind, start: a.start, destinations: destinations(a.destinations) },
+                    Action::Remove(id) => ScheduledAction::Remove { id },
+                    Action::SetDestinations { id, destinations: d } => ScheduledAction::SetDestinations { id, destinations: destinations(d) },
+                };
+                (e.at, k, action)
+            })
+            .collect();
+
+        Ok((s, Schedule { events, ids }))
+    }
+
+    fn sorted_events(&self) -> Vec<(usize, &Event<N, C, T>)> {
+        let mut events = self.events.iter().enumerate().collect::<Vec<_>>();
+        events.sort_by_key(|(k, e)| (e.at, *k));
+        events
+    }
+
+    fn kind_of(&self, id: &str) -> Option<&T> {
+        self.agents.iter()
+            .chain(self.events.iter().filter_map(|e| if let Action::Add(a) = &e.action { Some(a) } else { None }))
+            .find(|a| a.id == id)
+            .map(|a| &a.kind)
+    }
+}
+
+fn validate_agent<M: Map<U, T>, U: AgentIdxType, T>(map: &M, a: &AgentSpec<M::Node, M::Cost, T>, entry: &str) -> Result<(), ScenarioError> {
+    if a.id.is_empty() {
+        return Err(ScenarioError::invalid(format!("{}.id", entry), "id must not be empty"))
+    }
+    if !map.has_node(&a.start, &a.kind) {
+        return Err(ScenarioError::invalid(format!("{}.start", entry), "the node is not on the map"))
+    }
+    validate_destinations(map, &a.destinations, &a.kind, &format!("{}.destinations", entry))
+}
+
+fn validate_destinations<M: Map<U, T>, U: AgentIdxType, T>(map: &M, destinations: &[DestinationSpec<M::Node, M::Cost>], kind: &T, entry: &str) -> Result<(), ScenarioError> {
+    for (k, d) in destinations.iter().enumerate() {
+        let nodes = match d {
+            DestinationSpec::Nodes(ns) => ns.iter().collect::<Vec<_>>(),
+            DestinationSpec::Weighted(ws) => ws.iter().map(|w| &w.node).collect(),
+        };
+        if nodes.is_empty() {
+            return Err(ScenarioError::invalid(format!("{}[{}]", entry, k), "no nodes are given"))
+        }
+        let mut seen = HashSet::new();
+        for (l, n) in nodes.into_iter().enumerate() {
+            if !map.has_node(n, kind) {
+                return Err(ScenarioError::invalid(format!("{}[{}][{}]", entry, k, l), "the node is not on the map"))
+            }
+            if !seen.insert(n) {
+                return Err(ScenarioError::invalid(format!("{}[{}][{}]", entry, k, l), "the node appears twice"))
+            }
+        }
+    }
+    Ok(())
+}
+
+fn destinations<N: Node, C: Cost>(destinations: Vec<DestinationSpec<N, C>>) -> VecDeque<MultipleEnds<N, C>> {
+    destinations.into_iter()
+        .map(|d| match d {
+            DestinationSpec::Nodes(ns) => MultipleEnds::new_as_all_zero(ns),
+            DestinationSpec::Weighted(ws) => MultipleEnds::new(ws.into_iter().map(|w| (w.node, w.weight)).collect()),
+        })
+        .collect()
+}
+
+enum ScheduledAction<N: Node, C: Cost, T> {
+    Add { id: String, kind: T, start: N, destinations: VecDeque<MultipleEnds<N, C>> },
+    Remove { id: String },
+    SetDestinations { id: String, destinations: VecDeque<MultipleEnds<N, C>> },
+}
+
+// events of a scenario sorted by time
+pub struct Schedule<N: Node, C: Cost, T, U: AgentIdxType> {
+    // (time, index in `Scenario::events`, action)
+    events: VecDeque<(C, usize, ScheduledAction<N, C, T>)>,
+    ids: HashMap<String, Idx<T, U>>,
+}
+
+impl<N: Node, C: Cost, T, U: AgentIdxType + Ord + Hash> Schedule<N, C, T, U> {
+    pub fn is_empty(&self) -> bool { self.events.is_empty() }
+    pub fn len(&self) -> usize { self.events.len() }
+    pub fn next_time(&self) -> Option<C> { self.events.front().map(|(t, _, _)| *t) }
+
+    // index of an agent added so far
+    pub fn idx(&self, id: &str) -> Option<Idx<T, U>> { self.ids.get(id).copied() }
+    pub fn ids(&self) -> &HashMap<String, Idx<T, U>> { &self.ids }
+
+    // applies the events due at the current time of `s`, returns how many were applied
+    // an event which fails is dropped, and so are the later events of an agent whose addition failed
+    pub fn apply<M: Map<U, T, Node = N, Cost = C>>(&mut self, s: &mut Simulator<M, U, T>) -> Result<usize, ScenarioError> where N: Debug, U: Debug, M::SeatIndex: Hash + Debug {
+        let mut n = 0;
+        while self.events.front().is_some_and(|(t, _, _)| *t <= s.time()) {
+            let (_, k, action) = self.events.pop_front().unwrap();
+            let not_added = |entry: String, id: &str| ScenarioError::invalid(entry, format!("agent '{}' was not added", id));
+            match action {
+                ScheduledAction::Add { id, kind, start, destinations } => {
+                    let idx = s.add(kind, start, destinations).map_err(|e| ScenarioError::invalid(format!("events[{}].add", k), e))?;
+                    self.ids.insert(id, idx);
+                },
+                ScheduledAction::Remove { id } => {
+                    let entry = format!("events[{}].remove", k);
+                    let &idx = self.ids.get(&id).ok_or_else(|| not_added(entry.clone(), &id))?;
+                    s.remove(idx).map_err(|e| ScenarioError::invalid(entry, e))?;
+                },
+                ScheduledAction::SetDestinations { id, destinations } => {
+                    let &idx = self.ids.get(&id).ok_or_else(|| not_added(format!("events[{}].set_destinations.id", k), &id))?;
+                    if let Some(d) = s.agent_destination_mut(idx) {
+                        *d = destinations;
+                    }
+                },
+            }
+            n += 1;
+        }
+        Ok(n)
+    }
+
+    // steps `s` applying the events, and then runs until idle
+    pub fn run<M: Map<U, T, Node = N, Cost = C>>(&mut self, s: &mut Simulator<M, U, T>, max_steps: usize) -> Result<StopReason, ScenarioError> where N: Debug, U: Debug, M::SeatIndex: Hash + Debug {
+        self.run_with(s, max_steps, |_| {})
+    }
+
+    pub fn run_with<M: Map<U, T, Node = N, Cost = C>, F: FnMut(&Simulator<M, U, T>)>(&mut self, s: &mut Simulator<M, U, T>, max_steps: usize, mut f: F) -> Result<StopReason, ScenarioError> where N: Debug, U: Debug, M::SeatIndex: Hash + Debug {
+        let error = |e: Error<N, M::SeatIndex, T, U>| ScenarioError::Simulator(e.to_string());
+        for n in 0..max_steps {
+            self.apply(s)?;
+            if self.is_empty() {
+                return s.run_until_idle_with(max_steps - n, f).map_err(error)
+            }
+            s.step().map_err(error)?;
+            f(s);
+        }
+        Ok(StopReason::StepLimit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{grid::map::GridMap, simulator::{Clock, Planner, StopReason}};
+
+    use super::{MapFormat, Scenario, ScenarioError};
+
+    const TOML: &str = r#"
+        [map]
+        format = "ascii"
+        path = "grid.txt"
+
+        [settings]
+        max_reservation_time = 3
+        planner = "Dijkstra"
+
+        [[agents]]
+        id = "a"
+        start = [0, 0]
+        destinations = [[[4, 0]], [{ node = [0, 2], weight = 0 }, { node = [4, 2], weight = 3 }]]
+
+        [[events]]
+        at = 2
+        add = { id = "b", start = [4, 2], destinations = [[[0, 2]]] }
+
+        [[events]]
+        at = 4
+        set_destinations = { id = "b", destinations = [[[2, 2]]] }
+
+        [[events]]
+        at = 30
+        remove = "a"
+    "#;
+
+    #[test]
+    fn load_test() {
+        let scenario = Scenario::<(usize, usize), u32>::from_toml(TOML).unwrap();
+
+        assert_eq!(scenario.map.as_ref().map(|m| m.format), Some(MapFormat::Ascii));
+        assert_eq!(scenario.settings.clock, Clock::Tick);
+        assert_eq!(scenario.settings.planner, Planner::Dijkstra);
+        assert_eq!(scenario.agents[0].destinations.len(), 2);
+        assert_eq!(scenario.events.len(), 3);
+
+        let json = serde_json::to_string(&scenario).unwrap();
+        let scenario = Scenario::<(usize, usize), u32>::from_json(&json).unwrap();
+        assert_eq!(scenario.events.len(), 3);
+    }
+
+    #[test]
+    fn build_test() {
+        let scenario = Scenario::<(usize, usize), u32>::from_toml(TOML).unwrap();
+        let (mut s, mut schedule) = scenario.build(GridMap::<u32>::new(5, 3)).unwrap();
+
+        assert_eq!(s.planner(), Planner::Dijkstra);
+        assert_eq!(s.agents().len(), 1);
+        assert_eq!(schedule.next_time(), Some(2));
+
+        s.run_for(2, |_| {}).unwrap();
+        assert_eq!(schedule.apply(&mut s).unwrap(), 1);
+        assert_eq!(s.agents().len(), 2);
+
+        assert_eq!(schedule.run(&mut s, 100).unwrap(), StopReason::Completed);
+        assert!(schedule.is_empty());
+        assert!(s.agent(schedule.idx("a").unwrap()).is_none());
+        assert_eq!(s.agent(schedule.idx("b").unwrap()).map(|a| *a.current()), Some((2, 2)));
+    }
+
+    #[test]
+    fn failed_event_test() {
+        // "b" is walled in, so it cannot reach its destination and is not added
+        let text = TOML.replace(", { node = [4, 2], weight = 3 }", "").replace("remove = \"a\"", "remove = \"b\"");
+        let scenario = Scenario::<(usize, usize), u32>::from_toml(&text).unwrap();
+        let (mut s, mut schedule) = scenario.build(GridMap::<u32>::new_with_blocked(5, 3, &[(3, 2), (4, 1)])).unwrap();
+
+        let entry = |e: Option<ScenarioError>| match e {
+            Some(ScenarioError::Invalid { entry, .. }) => entry,
+            e => panic!("unexpected error: {:?}", e),
+        };
+        s.run_for(2, |_| {}).unwrap();
+        assert_eq!(entry(schedule.apply(&mut s).err()), "events[0].add");
+        s.run_for(2, |_| {}).unwrap();
+        assert_eq!(entry(schedule.apply(&mut s).err()), "events[1].set_destinations.id");
+        assert_eq!(entry(schedule.run(&mut s, 100).err()), "events[2].remove");
+        assert!(schedule.is_empty());
+    }
+
+    #[test]
+    fn validation_test() {
+        let cases = [
+            (TOML.replace("start = [0, 0]", "start = [5, 0]"), "agents[0].start"),
+            (TOML.replace("weight = 3", "weight = 3 }, { node = [9, 9], weight = 0"), "agents[0].destinations[1][2]"),
+            (TOML.replace("[[[4, 0]], [{", "[[], [{"), "agents[0].destinations[0]"),
+            (TOML.replace("id = \"b\", start", "id = \"a\", start"), "events[0].add.id"),
+            (TOML.replace("remove = \"a\"", "remove = \"c\""), "events[2].remove"),
+            (TOML.replace("at = 2", "at = 5"), "events[1].set_destinations.id"),
+            (TOML.replace("destinations = [[[2, 2]]]", "destinations = [[[2, 2], [2, 2]]]"), "events[1].set_destinations.destinations[0][1]"),
+        ];
+
+        for (text, entry) in cases {
+            let scenario = Scenario::<(usize, usize), u32>::from_toml(&text).unwrap();
+            match scenario.build(GridMap::<u32>::new_with_blocked(5, 3, &[(1, 1)])) {
+                Err(ScenarioError::Invalid { entry: e, .. }) => assert_eq!(e, entry),
+                Err(e) => panic!("unexpected error: {}", e),
+                Ok(_) => panic!("{} is not detected", entry),
+            }
+        }
+
+        assert!(matches!(Scenario::<(usize, usize), u32>::from_toml("[settings]"), Err(ScenarioError::Parse(_))));
+    }
+}
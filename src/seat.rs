@@ -1,15 +1,413 @@
+use std::{error::Error, fmt::{Debug, Display, Formatter}, sync::Arc};
+
 use num_traits::bounds::UpperBounded;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use trait_set::trait_set;
 
 use crate::index::index::{Idx, IdxType};
 
 trait_set! {
-    pub trait AgentIdxType = IdxType + UpperBounded
+    // 'static and thread safe so that allocators of indices can be boxed
+    pub trait AgentIdxType = IdxType + UpperBounded + Send + Sync + 'static
+}
+
+// why a seat refused `add` or `remove`, the seat is left unchanged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeatError {
+    // other agents hold the seat up to its capacity
+    AlreadyOccupied,
+    // the kind of the agent cannot share the seat with its holders
+    Incompatible,
+    Blocked,
+    // the agent to be released does not hold the seat
+    NotHeld,
 }
 
+impl Display for SeatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AlreadyOccupied => write!(f, "the seat is already occupied"),
+            Self::Incompatible => write!(f, "the seat is held by an agent of an incompatible kind"),
+            Self::Blocked => write!(f, "the seat is blocked"),
+            Self::NotHeld => write!(f, "the seat is not held by the agent"),
+        }
+    }
+}
+
+impl Error for SeatError {}
+
 pub trait Seat<T, U: AgentIdxType> {
 
     fn is_empty_for(&self, idx: Idx<T, U>) -> bool;
-    fn add(&mut self, idx: Idx<T, U>);
-    fn remove(&mut self, idx: Idx<T, U>);
+    // adding a holder again succeeds without changing anything
+    fn add(&mut self, idx: Idx<T, U>) -> Result<(), SeatError>;
+    fn remove(&mut self, idx: Idx<T, U>) -> Result<(), SeatError>;
+
+    // guessed from `is_empty_for` with max_value, which is never assigned to an agent
+    fn is_held_by(&self, idx: Idx<T, U>) -> bool {
+        self.is_empty_for(idx) && !self.is_empty_for(Idx::new(U::max_value()))
+    }
+
+    // how many agents may hold the seat at the same time
+    fn capacity(&self) -> usize { 1 }
+
+    // the simulator asks with the kind of the agent, seats which do not care about kinds need not implement these
+    fn is_empty_for_kind(&self, idx: Idx<T, U>, _kind: &T) -> bool {
+        self.is_empty_for(idx)
+    }
+
+    fn add_with_kind(&mut self, idx: Idx<T, U>, _kind: &T) -> Result<(), SeatError> {
+        self.add(idx)
+    }
+}
+
+// held by up to `capacity` agents at the same time
+// adding a holder twice keeps one entry, so an agent reserving the seat again in a later plan does not take another place
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(serialize = "U: Serialize", deserialize = "U: Deserialize<'de>")))]
+pub struct CapacitySeat<T, U: AgentIdxType> {
+    capacity: usize,
+    holders: Vec<Idx<T, U>>,
+}
+
+impl<T, U: AgentIdxType> CapacitySeat<T, U> {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, holders: vec![] }
+    }
+
+    pub fn holders(&self) -> &[Idx<T, U>] { &self.holders }
+    pub fn is_full(&self) -> bool { self.holders.len() >= self.capacity }
+}
+
+impl<T, U: AgentIdxType> Clone for CapacitySeat<T, U> {
+    fn clone(&self) -> Self {
+        Self { capacity: self.capacity, holders: self.holders.clone() }
+    }
+}
+
+impl<T, U: AgentIdxType + Debug> Debug for CapacitySeat<T, U> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CapacitySeat").field("capacity", &self.capacity).field("holders", &self.holders).finish()
+    }
+}
+
+impl<T, U: AgentIdxType> Seat<T, U> for CapacitySeat<T, U> {
+    fn is_empty_for(&self, idx: Idx<T, U>) -> bool {
+        self.holders.contains(&idx) || !self.is_full()
+    }
+
+    fn add(&mut self, idx: Idx<T, U>) -> Result<(), SeatError> {
+        if !self.holders.contains(&idx) {
+            if self.is_full() {
+                return Err(SeatError::AlreadyOccupied)
+            }
+            self.holders.push(idx);
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, idx: Idx<T, U>) -> Result<(), SeatError> {
+        let k = self.holders.iter().position(|&i| i == idx).ok_or(SeatError::NotHeld)?;
+        self.holders.swap_remove(k);
+        Ok(())
+    }
+
+    fn is_held_by(&self, idx: Idx<T, U>) -> bool {
+        self.holders.contains(&idx)
+    }
+
+    fn capacity(&self) -> usize { self.capacity }
+}
+
+// numbers the kinds of agents for `CompatibilityMatrix`
+pub trait KindIndex {
+    fn kind_index(&self) -> usize;
+}
+
+impl KindIndex for () {
+    fn kind_index(&self) -> usize { 0 }
+}
+
+impl KindIndex for usize {
+    fn kind_index(&self) -> usize { *self }
+}
+
+// which kinds of agents may hold a seat together, symmetric and nothing is allowed by default
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CompatibilityMatrix {
+    n_kinds: usize,
+    allowed: Vec<bool>,
+}
+
+impl CompatibilityMatrix {
+    pub fn new(n_kinds: usize) -> Self {
+        Self { n_kinds, allowed: vec![false; n_kinds * n_kinds] }
+    }
+
+    pub fn n_kinds(&self) -> usize { self.n_kinds }
+
+    pub fn allow(&mut self, a: usize, b: usize) {
+        self.set(a, b, true)
+    }
+
+    pub fn set(&mut self, a: usize, b: usize, allowed: bool) {
+        assert!(a < self.n_kinds && b < self.n_kinds, "kind index out of range");
+        self.allowed[a * self.n_kinds + b] = allowed;
+        self.allowed[b * self.n_kinds + a] = allowed;
+    }
+
+    // kinds out of range are compatible with nothing
+    pub fn is_compatible(&self, a: usize, b: usize) -> bool {
+        a < self.n_kinds && b < self.n_kinds && self.allowed[a * self.n_kinds + b]
+    }
+}
+
+// held by up to `capacity` agents whose kinds are compatible with each other, the matrix is shared by the seats of a map
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(serialize = "U: Serialize", deserialize = "U: Deserialize<'de>")))]
+pub struct CompatibleSeat<T, U: AgentIdxType> {
+    matrix: Arc<CompatibilityMatrix>,
+    capacity: usize,
+    // (holder, kind index), the kind is unknown (usize::MAX) if added by `add`
+    holders: Vec<(Idx<T, U>, usize)>,
+}
+
+impl<T, U: AgentIdxType> CompatibleSeat<T, U> {
+    pub fn new(matrix: Arc<CompatibilityMatrix>, capacity: usize) -> Self {
+        Self { matrix, capacity, holders: vec![] }
+    }
+
+    pub fn matrix(&self) -> &CompatibilityMatrix { &self.matrix }
+    pub fn holders(&self) -> &[(Idx<T, U>, usize)] { &self.holders }
+
+    fn contains(&self, idx: Idx<T, U>) -> bool {
+        self.holders.iter().any(|&(i, _)| i == idx)
+    }
+
+    // whether the agent of the kind can hold the seat now
+    fn check(&self, idx: Idx<T, U>, kind: usize) -> Result<(), SeatError> {
+        if self.contains(idx) {
+            Ok(())
+        } else if self.holders.len() >= self.capacity {
+            Err(SeatError::AlreadyOccupied)
+        } else if !self.holders.iter().all(|&(_, h)| self.matrix.is_compatible(kind, h)) {
+            Err(SeatError::Incompatible)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn insert(&mut self, idx: Idx<T, U>, kind: usize) -> Result<(), SeatError> {
+        self.check(idx, kind)?;
+        if !self.contains(idx) {
+            self.holders.push((idx, kind));
+        }
+        Ok(())
+    }
+}
+
+impl<T, U: AgentIdxType> Clone for CompatibleSeat<T, U> {
+    fn clone(&self) -> Self {
+        Self { matrix: self.matrix.clone(), capacity: self.capacity, holders: self.holders.clone() }
+    }
+}
+
+impl<T, U: AgentIdxType + Debug> Debug for CompatibleSeat<T, U> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompatibleSeat").field("capacity", &self.capacity).field("holders", &self.holders).finish()
+    }
+}
+
+impl<T: KindIndex, U: AgentIdxType> Seat<T, U> for CompatibleSeat<T, U> {
+    // without the kind only an empty seat is known to be available
+    fn is_empty_for(&self, idx: Idx<T, U>) -> bool {
+        self.check(idx, usize::MAX).is_ok()
+    }
+
+    fn add(&mut self, idx: Idx<T, U>) -> Result<(), SeatError> {
+        self.insert(idx, usize::MAX)
+    }
+
+    fn remove(&mut self, idx: Idx<T, U>) -> Result<(), SeatError> {
+        let k = self.holders.iter().position(|&(i, _)| i == idx).ok_or(SeatError::NotHeld)?;
+        self.holders.swap_remove(k);
+        Ok(())
+    }
+
+    fn is_held_by(&self, idx: Idx<T, U>) -> bool {
+        self.contains(idx)
+    }
+
+    fn capacity(&self) -> usize { self.capacity }
+
+    fn is_empty_for_kind(&self, idx: Idx<T, U>, kind: &T) -> bool {
+        self.check(idx, kind.kind_index()).is_ok()
+    }
+
+    fn add_with_kind(&mut self, idx: Idx<T, U>, kind: &T) -> Result<(), SeatError> {
+        self.insert(idx, kind.kind_index())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, iter::{once, Once}, ops::{Index, IndexMut, Range}, sync::Arc, vec::IntoIter};
+
+    use crate::{index::index::Idx, map::{DummyHeuristic, Map}, pathfind::common::MultipleEnds, simulator::{Simulator, StopReason}};
+
+    use super::{CapacitySeat, CompatibilityMatrix, CompatibleSeat, KindIndex, Seat, SeatError};
+
+    // a line of seats on which an agent covers `width` consecutive seats from its node
+    struct LineMap<S> {
+        seats: Vec<S>,
+        width: usize,
+    }
+
+    impl<S> LineMap<S> {
+        fn new<F: Fn() -> S>(n: usize, width: usize, seat: F) -> Self {
+            Self { seats: (0..n).map(|_| seat()).collect(), width }
+        }
+    }
+
+    impl<T, S: Seat<T, u32>> Map<u32, T> for LineMap<S> {
+        type Cost = u32;
+        type I = usize;
+        type SeatIndex = usize;
+        type Seat = S;
+        type Node = usize;
+
+        type SIter = Range<usize>;
+        type SCIter = IntoIter<(Self::I, Self::Node, Self::Cost)>;
+        type SBIter = Once<(Self::SeatIndex, Self::Cost)>;
+
+        type FH = DummyHeuristic;
+
+        fn seats(&self, &n: &Self::Node, _: &T) -> Self::SIter {
+            n..n + self.width
+        }
+
+        fn successors(&self, n: &Self::Node, t: &T) -> Self::SCIter {
+            (0..2).filter_map(|i| self.successor(n, t, &i).map(|m| (i, m, 1))).collect::<Vec<_>>().into_iter()
+        }
+
+        fn successor(&self, &n: &Self::Node, _: &T, &i: &Self::I) -> Option<Self::Node> {
+            match i {
+                0 if n + self.width < self.seats.len() => Some(n + 1),
+                1 if n > 0 => Some(n - 1),
+                _ => None,
+            }
+        }
+
+        // the seat the agent leaves is held until the arrival
+        fn seats_between(&self, &n: &Self::Node, _: &T, &i: &Self::I) -> Self::SBIter {
+            once((if i == 0 { n } else { n + self.width - 1 }, 1))
+        }
+    }
+
+    impl<S> Index<usize> for LineMap<S> {
+        type Output = S;
+        fn index(&self, i: usize) -> &Self::Output { &self.seats[i] }
+    }
+
+    impl<S> IndexMut<usize> for LineMap<S> {
+        fn index_mut(&mut self, i: usize) -> &mut Self::Output { &mut self.seats[i] }
+    }
+
+    #[test]
+    fn seat_test() {
+        let (i0, i1, i2) = (Idx::new(0), Idx::new(1), Idx::new(2));
+        let mut seat = CapacitySeat::<(), u32>::new(2);
+
+        assert_eq!(seat.add(i0), Ok(()));
+        assert_eq!(seat.add(i0), Ok(()));
+        assert_eq!(seat.holders(), &[i0]);
+        assert!(seat.is_empty_for(i1));
+
+        assert_eq!(seat.add(i1), Ok(()));
+        assert!(seat.is_full());
+        assert!(seat.is_empty_for(i0) && !seat.is_empty_for(i2));
+        assert!(seat.is_held_by(i1) && !seat.is_held_by(i2));
+        assert_eq!(seat.add(i2), Err(SeatError::AlreadyOccupied));
+
+        assert_eq!(seat.remove(i0), Ok(()));
+        assert_eq!(seat.remove(i0), Err(SeatError::NotHeld));
+        assert_eq!(seat.holders(), &[i1]);
+        assert!(seat.is_empty_for(i2));
+    }
+
+    #[test]
+    fn simulator_test() {
+        // agents covering 2 seats pass each other where 2 agents fit
+        let mut s = Simulator::new(0, LineMap::new(8, 2, || CapacitySeat::new(2)), 3);
+        s.set_validation(true);
+        let idxs = [(0, 6), (6, 0), (3, 6)]
+            .map(|(n, m)| s.add((), n, VecDeque::from([MultipleEnds::new_as_all_zero(vec![m])])).unwrap());
+
+        let mut max_holders = 0;
+        let reason = s.run_until_idle_with(100, |s| {
+            max_holders = max_holders.max(s.map().seats.iter().map(|seat| seat.holders().len()).max().unwrap());
+        }).unwrap();
+
+        assert_eq!(reason, StopReason::Completed);
+        assert_eq!(max_holders, 2);
+        assert_eq!(idxs.map(|i| *s.agent(i).unwrap().current()), [6, 0, 6]);
+        // every reservation on the way is released
+        assert_eq!(s.map().seats.iter().map(|seat| seat.holders().len()).collect::<Vec<_>>(), vec![1, 1, 0, 0, 0, 0, 2, 2]);
+    }
+
+    #[derive(Clone, Copy)]
+    enum Kind {
+        Pedestrian,
+        Forklift,
+    }
+
+    impl KindIndex for Kind {
+        fn kind_index(&self) -> usize { *self as usize }
+    }
+
+    fn matrix() -> Arc<CompatibilityMatrix> {
+        let mut matrix = CompatibilityMatrix::new(2);
+        matrix.allow(Kind::Pedestrian as usize, Kind::Pedestrian as usize);
+        Arc::new(matrix)
+    }
+
+    #[test]
+    fn compatible_seat_test() {
+        let (i0, i1, i2) = (Idx::new(0), Idx::new(1), Idx::new(2));
+        let mut seat = CompatibleSeat::<Kind, u32>::new(matrix(), 2);
+
+        assert_eq!(seat.add_with_kind(i0, &Kind::Pedestrian), Ok(()));
+        assert!(seat.is_empty_for_kind(i1, &Kind::Pedestrian));
+        assert!(!seat.is_empty_for_kind(i1, &Kind::Forklift));
+        assert!(!seat.is_empty_for(i1));
+        assert_eq!(seat.add(i1), Err(SeatError::Incompatible));
+
+        assert_eq!(seat.add_with_kind(i1, &Kind::Pedestrian), Ok(()));
+        assert!(!seat.is_empty_for_kind(i2, &Kind::Pedestrian));
+        assert!(seat.is_empty_for_kind(i0, &Kind::Pedestrian));
+        assert_eq!(seat.add_with_kind(i2, &Kind::Pedestrian), Err(SeatError::AlreadyOccupied));
+
+        assert_eq!(seat.remove(i0), Ok(()));
+        assert_eq!(seat.add_with_kind(i2, &Kind::Forklift), Err(SeatError::Incompatible));
+        assert_eq!(seat.remove(i1), Ok(()));
+        assert_eq!(seat.add_with_kind(i2, &Kind::Forklift), Ok(()));
+        assert!(!seat.is_empty_for_kind(i0, &Kind::Forklift));
+        assert!(!seat.is_empty_for_kind(i0, &Kind::Pedestrian));
+    }
+
+    #[test]
+    fn compatible_simulator_test() {
+        // pedestrians pass each other on a line only one agent wide, a forklift and a pedestrian do not
+        let matrix = matrix();
+        for (kinds, expected) in [([Kind::Pedestrian, Kind::Pedestrian], StopReason::Completed), ([Kind::Forklift, Kind::Pedestrian], StopReason::Deadlock)] {
+            let mut s = Simulator::new(0, LineMap::new(6, 1, || CompatibleSeat::new(matrix.clone(), 2)), 3);
+            s.set_validation(true);
+            s.add(kinds[0], 0, VecDeque::from([MultipleEnds::new_as_all_zero(vec![5])])).unwrap();
+            s.add(kinds[1], 5, VecDeque::from([MultipleEnds::new_as_all_zero(vec![0])])).unwrap();
+
+            assert_eq!(s.run_until_idle(100).unwrap(), expected);
+        }
+    }
 }
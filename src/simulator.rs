@@ -1,84 +1,480 @@
-use std::{collections::{BTreeMap, BinaryHeap, HashMap, VecDeque}, hash::Hash, marker::PhantomData};
+use std::{collections::{BTreeMap, BinaryHeap, HashMap, HashSet, VecDeque}, error::Error as StdError, fmt::{Debug, Display, Formatter}, hash::Hash, marker::PhantomData, time::Instant};
 
 use num_traits::One;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-use crate::{agent_data::{AgentData, AgentState}, duration::Duration, index::index::Idx, map::{Map, Movement}, pathfind::{astar::astar_for_next_reservation, common::MultipleEnds, dijkstra::dijkstra_for_next_reservation}, seat::{AgentIdxType, Seat}};
+use crate::{agent_data::{AgentData, AgentState, RemovalMode}, duration::Duration, error::Error, index::{allocator::{Allocator, IdxAllocator}, index::Idx}, map::{Map, Movement}, pathfind::{astar::astar_for_next_reservation, common::MultipleEnds, dijkstra::dijkstra_for_next_reservation}, seat::{AgentIdxType, Seat}};
 
 use crate::map::Heuristic;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Clock {
+    // advances `time` by `Cost::one()` each step
+    #[default]
+    Tick,
+    // advances `time` to the next seat release or arrival
+    Event,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Planner {
+    // uses the heuristic of the map if any, otherwise dijkstra
+    #[default]
+    AStar,
+    Dijkstra,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StopReason {
+    // every agent is placed and has no destinations left
+    Completed,
+    // no agent can move anymore
+    Deadlock,
+    StepLimit,
+}
+
+// what happened to an agent during the last step
+pub enum StepEvent<T, U: AgentIdxType> {
+    Placed(Idx<T, U>),
+    // the agent reached the end of its reserved path, `reached` if the end was one of its next destinations
+    Arrived { idx: Idx<T, U>, reached: bool },
+    // a path search for an agent which has destinations left, `success` if the agent departs
+    Planned { idx: Idx<T, U>, success: bool, elapsed: std::time::Duration },
+    Removed { idx: Idx<T, U>, mode: RemovalMode },
+}
+
+impl<T, U: AgentIdxType> StepEvent<T, U> {
+    pub fn idx(&self) -> Idx<T, U> {
+        match self {
+            Self::Placed(idx) => *idx,
+            Self::Arrived { idx, .. } | Self::Planned { idx, .. } | Self::Removed { idx, .. } => *idx,
+        }
+    }
+}
+
+impl<T, U: AgentIdxType> Clone for StepEvent<T, U> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, U: AgentIdxType> Copy for StepEvent<T, U> {}
+
+impl<T, U: AgentIdxType> PartialEq for StepEvent<T, U> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Placed(a), Self::Placed(b)) => a == b,
+            (Self::Arrived { idx: a, reached: r }, Self::Arrived { idx: b, reached: s }) => a == b && r == s,
+            (Self::Planned { idx: a, success: r, elapsed: d }, Self::Planned { idx: b, success: s, elapsed: e }) => a == b && r == s && d == e,
+            (Self::Removed { idx: a, mode: m }, Self::Removed { idx: b, mode: n }) => a == b && m == n,
+            _ => false,
+        }
+    }
+}
+
+impl<T, U: AgentIdxType + Debug> Debug for StepEvent<T, U> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Placed(idx) => f.debug_tuple("Placed").field(idx).finish(),
+            Self::Arrived { idx, reached } => f.debug_struct("Arrived").field("idx", idx).field("reached", reached).finish(),
+            Self::Planned { idx, success, elapsed } => f.debug_struct("Planned").field("idx", idx).field("success", success).field("elapsed", elapsed).finish(),
+            Self::Removed { idx, mode } => f.debug_struct("Removed").field("idx", idx).field("mode", mode).finish(),
+        }
+    }
+}
+
+// an inconsistency between the agents and the seats found by `Simulator::validate`
+pub enum Violation<S, T, U: AgentIdxType> {
+    // a seat under a stopping agent or on its reserved path is not held by it
+    NotHeld { idx: Idx<T, U>, seat: S },
+    // more agents than the capacity hold the seat
+    OverBooked { seat: S, holders: Vec<Idx<T, U>> },
+    // a pending release refers to an agent which does not exist
+    DeadHolder { idx: Idx<T, U>, seat: S },
+    // the arrival times of `Moving { nexts }` decrease
+    NonMonotonic { idx: Idx<T, U> },
+}
+
+impl<S: Clone, T, U: AgentIdxType> Clone for Violation<S, T, U> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::NotHeld { idx, seat } => Self::NotHeld { idx: *idx, seat: seat.clone() },
+            Self::OverBooked { seat, holders } => Self::OverBooked { seat: seat.clone(), holders: holders.clone() },
+            Self::DeadHolder { idx, seat } => Self::DeadHolder { idx: *idx, seat: seat.clone() },
+            Self::NonMonotonic { idx } => Self::NonMonotonic { idx: *idx },
+        }
+    }
+}
+
+impl<S: PartialEq, T, U: AgentIdxType> PartialEq for Violation<S, T, U> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::NotHeld { idx: a, seat: s }, Self::NotHeld { idx: b, seat: t }) => a == b && s == t,
+            (Self::OverBooked { seat: s, holders: a }, Self::OverBooked { seat: t, holders: b }) => s == t && a == b,
+            (Self::DeadHolder { idx: a, seat: s }, Self::DeadHolder { idx: b, seat: t }) => a == b && s == t,
+            (Self::NonMonotonic { idx: a }, Self::NonMonotonic { idx: b }) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl<S: Debug, T, U: AgentIdxType + Debug> Debug for Violation<S, T, U> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotHeld { idx, seat } => f.debug_struct("NotHeld").field("idx", idx).field("seat", seat).finish(),
+            Self::OverBooked { seat, holders } => f.debug_struct("OverBooked").field("seat", seat).field("holders", holders).finish(),
+            Self::DeadHolder { idx, seat } => f.debug_struct("DeadHolder").field("idx", idx).field("seat", seat).finish(),
+            Self::NonMonotonic { idx } => f.debug_struct("NonMonotonic").field("idx", idx).finish(),
+        }
+    }
+}
+
+impl<S: Debug, T, U: AgentIdxType + Debug> Display for Violation<S, T, U> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotHeld { idx, seat } => write!(f, "seat {:?} is not held by agent {:?}", seat, idx.value()),
+            Self::OverBooked { seat, holders } => write!(f, "seat {:?} is held by {:?}", seat, holders.iter().map(|i| i.value()).collect::<Vec<_>>()),
+            Self::DeadHolder { idx, seat } => write!(f, "seat {:?} is to be released for agent {:?} which does not exist", seat, idx.value()),
+            Self::NonMonotonic { idx } => write!(f, "arrival times of agent {:?} are not monotonic", idx.value()),
+        }
+    }
+}
+
+impl<S: Debug, T, U: AgentIdxType + Debug> StdError for Violation<S, T, U> {}
+
+#[allow(clippy::type_complexity)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(
+    serialize = "M: Serialize, M::Node: Serialize, M::Cost: Serialize, M::SeatIndex: Serialize, U: Serialize, T: Serialize",
+    deserialize = "M: Deserialize<'de>, M::Node: Deserialize<'de>, M::Cost: Deserialize<'de>, M::SeatIndex: Deserialize<'de>, U: Deserialize<'de>, T: Deserialize<'de>",
+)))]
 pub struct Simulator<M: Map<U, T>, U: AgentIdxType + Ord, T = ()> 
 {
     time: M::Cost,
+    clock: Clock,
+    planner: Planner,
     map: M,
     durations: BinaryHeap<Duration<M::Cost, M::SeatIndex, T, U>>,
     agents: BTreeMap<Idx<T, U>, AgentData<M::Node, M::Cost, T>>,
+    // seats held by each placed agent with their release times, None until the agent departs
+    reservations: BTreeMap<Idx<T, U>, Vec<(M::SeatIndex, Option<M::Cost>)>>,
     queue: VecDeque<Idx<T, U>>,
     max_reservation_time: M::Cost,
+    // whether the next step may change the state even without any event
+    dirty: bool,
+    // time at which the last step started and what happened in it
+    last_step_time: M::Cost,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    events: Vec<StepEvent<T, U>>,
+    // `validate` after every step
+    #[cfg_attr(feature = "serde", serde(skip))]
+    validation: bool,
+    // a snapshot without it, e.g. an older one, counts up from its largest index
+    #[cfg_attr(feature = "serde", serde(default))]
+    allocator: Allocator<U>,
+    // an error left the state partially updated, see `is_poisoned`
+    #[cfg_attr(feature = "serde", serde(default))]
+    poisoned: bool,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent, bound(
+    serialize = "M: Serialize, M::Node: Serialize, M::Cost: Serialize, M::SeatIndex: Serialize, U: Serialize, T: Serialize",
+    deserialize = "M: Deserialize<'de>, M::Node: Deserialize<'de>, M::Cost: Deserialize<'de>, M::SeatIndex: Deserialize<'de>, U: Deserialize<'de>, T: Deserialize<'de>",
+)))]
+pub struct Snapshot<M: Map<U, T>, U: AgentIdxType + Ord, T = ()>(Simulator<M, U, T>);
+
+impl<M: Map<U, T>, U: AgentIdxType + Ord, T> Snapshot<M, U, T> {
+    pub fn time(&self) -> M::Cost { self.0.time }
 }
 
+#[allow(clippy::type_complexity)]
 impl<M: Map<U, T>, U: AgentIdxType + Ord, T> Simulator<M, U, T> where M::SeatIndex: Hash
 {
     pub fn new(init_time: M::Cost, map: M, max_reservation_time: M::Cost) -> Self {
+        Self::new_with_clock(init_time, map, max_reservation_time, Clock::Tick)
+    }
+
+    pub fn new_with_clock(init_time: M::Cost, map: M, max_reservation_time: M::Cost, clock: Clock) -> Self {
         Self {
             time: init_time,
+            clock,
+            planner: Planner::AStar,
             map,
             durations: BinaryHeap::new(),
             agents: BTreeMap::new(),
+            reservations: BTreeMap::new(),
             queue: VecDeque::new(),
             max_reservation_time,
+            dirty: false,
+            last_step_time: init_time,
+            events: vec![],
+            validation: false,
+            allocator: Allocator::default(),
+            poisoned: false,
         }
     }
 
+    pub fn snapshot(&self) -> Snapshot<M, U, T> where M: Clone, T: Clone {
+        Snapshot(self.clone())
+    }
+
+    pub fn restore(&mut self, snapshot: Snapshot<M, U, T>) {
+        *self = snapshot.0;
+    }
+
+    pub fn time(&self) -> M::Cost { self.time }
+    pub fn clock(&self) -> Clock { self.clock }
+    pub fn planner(&self) -> Planner { self.planner }
+    pub fn set_planner(&mut self, planner: Planner) { self.planner = planner }
+    // indices of the agents added afterwards are chosen by `allocator`
+    pub fn set_allocator<A: Into<Allocator<U>>>(&mut self, allocator: A) { self.allocator = allocator.into() }
+    pub fn validation(&self) -> bool { self.validation }
+    // a seat error in the middle of `step`, `cancel_path` or `replan` leaves the state partially updated,
+    // then `step` and the methods changing the agents return `Error::Poisoned` until a snapshot is restored
+    pub fn is_poisoned(&self) -> bool { self.poisoned }
     pub fn map(&self) -> &M { &self.map }
+    pub fn last_step_time(&self) -> M::Cost { self.last_step_time }
+    pub fn events(&self) -> &[StepEvent<T, U>] { &self.events }
 
     pub fn agents(&self) -> &BTreeMap<Idx<T, U>, AgentData<M::Node, M::Cost, T>> { &self.agents }
     pub fn agent(&self, idx: Idx<T, U>) -> Option<&AgentData<M::Node, M::Cost, T>> { self.agents.get(&idx) }
 
     pub fn agent_destination_mut(&mut self, idx: Idx<T, U>) -> Option<&mut VecDeque<MultipleEnds<<M as Map<U, T>>::Node, <M as Map<U, T>>::Cost>>> {
-        self.agents.get_mut(&idx)
-            .and_then(|a| Some(a.destinations_mut()))
+        let a = self.agents.get_mut(&idx)?;
+        self.dirty = true;
+        Some(a.destinations_mut())
+    }
+
+    // seats held by the agent with their scheduled release times, None for the seats held until it departs
+    pub fn reserved_seats(&self, idx: Idx<T, U>) -> Result<&[(M::SeatIndex, Option<M::Cost>)], Error<M::Node, M::SeatIndex, T, U>> {
+        if !self.agents.contains_key(&idx) {
+            return Err(Error::UnknownAgent(idx))
+        }
+        Ok(self.reservations.get(&idx).map_or(&[], |r| r.as_slice()))
     }
 
-    pub fn movement_of(&self, idx: Idx<T, U>, index: M::I) -> Option<Movement<M, U, T>> {
-        let Some(a) = self.agent(idx) else { return None };
+    pub fn movement_of(&self, idx: Idx<T, U>, index: M::I) -> Result<Movement<M, U, T>, Error<M::Node, M::SeatIndex, T, U>> {
+        let a = self.agent(idx).ok_or(Error::UnknownAgent(idx))?;
 
         let c = if let AgentState::Moving { nexts } = a.state() {
             &nexts[nexts.len() - 1].0
         } else {
             a.current()
         };
-        self.map.movement(c, a.kind(), &index)
+        self.map.movement(c, a.kind(), &index).ok_or_else(|| Error::NoMovement { idx, node: c.clone() })
     }
 
     pub fn is_empty_for(&self, idx: Idx<T, U>, s: &Movement<M, U, T>) -> bool {
+        let kind = self.agent(idx).map(|a| a.kind());
         s.seats()
             .iter()
-            .all(|(s, _)| self.map[s.clone()].is_empty_for(idx))
+            .map(|(s, _)| &self.map[s.clone()])
+            .all(|s| kind.map_or_else(|| s.is_empty_for(idx), |k| s.is_empty_for_kind(idx, k)))
+    }
+
+    // the agent waits until the seats of `node` are free, no destinations leave it where it is
+    pub fn add(&mut self, agent: T, node: M::Node, destination: VecDeque<MultipleEnds<M::Node, M::Cost>>) -> Result<Idx<T, U>, Error<M::Node, M::SeatIndex, T, U>> {
+        self.check_poisoned()?;
+        self.check_destinations(&agent, &node, &destination)?;
+        let idx = self.allocator.allocate(&self.agents).map(Idx::new).ok_or(Error::IdxExhausted)?;
+        self.insert(idx, agent, node, destination);
+        Ok(idx)
+    }
+
+    // the index is chosen by the caller, e.g. an id of an external system, instead of the allocator
+    pub fn add_with_idx(&mut self, idx: Idx<T, U>, agent: T, node: M::Node, destination: VecDeque<MultipleEnds<M::Node, M::Cost>>) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        self.check_poisoned()?;
+        if idx.value() == U::max_value() {
+            return Err(Error::NullIdx)
+        }
+        if self.agents.contains_key(&idx) {
+            return Err(Error::IdxInUse(idx))
+        }
+        self.check_destinations(&agent, &node, &destination)?;
+        self.insert(idx, agent, node, destination);
+        Ok(())
     }
 
-    pub fn add(&mut self, agent: T, node: M::Node, destination: VecDeque<MultipleEnds<M::Node, M::Cost>>) -> Idx<T, U> {
-        // node のバリデーション
-        let idx = self.new_idx();
+    fn insert(&mut self, idx: Idx<T, U>, agent: T, node: M::Node, destination: VecDeque<MultipleEnds<M::Node, M::Cost>>) {
         self.agents.insert(idx, AgentData::new(agent, node, destination));
         self.queue.push_back(idx);
-        idx
+        self.dirty = true;
     }
 
-    fn new_idx(&self) -> Idx<T, U> {
-        let Some(&i) = self.agents.keys().min() else { return Idx::new(U::zero()) };
-        
-        if i.value() != U::zero() {
-            return Idx::new(i.value() - U::one())
+    // destinations are searched in order on the map without agents
+    fn check_destinations(&self, kind: &T, node: &M::Node, destinations: &VecDeque<MultipleEnds<M::Node, M::Cost>>) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        if !self.map.has_node(node, kind) {
+            return Err(Error::InvalidNode(node.clone()))
+        }
+
+        let mut current = vec![node.clone()];
+        for (index, d) in destinations.iter().enumerate() {
+            if d.ends().is_empty() {
+                return Err(Error::EmptyDestination { index })
+            }
+            if let Some(n) = d.ends().keys().find(|n| !self.map.has_node(n, kind)) {
+                return Err(Error::InvalidDestination { index, node: n.clone() })
+            }
+
+            // stops once every end is found
+            let mut n_found = current.iter().filter(|n| d.ends().contains_key(n)).count();
+            let mut visited = current.iter().cloned().collect::<HashSet<_>>();
+            let mut queue = current.into_iter().collect::<VecDeque<_>>();
+            while let Some(n) = queue.pop_front().filter(|_| n_found < d.ends().len()) {
+                for (_, m, _) in self.map.successors(&n, kind) {
+                    if visited.insert(m.clone()) {
+                        n_found += d.ends().contains_key(&m) as usize;
+                        queue.push_back(m);
+                    }
+                }
+            }
+            current = d.ends().keys().filter(|n| visited.contains(n)).cloned().collect();
+            if current.is_empty() {
+                return Err(Error::UnreachableDestination { index })
+            }
+        }
+        Ok(())
+    }
+
+    // the agent leaves when it stops next
+    pub fn remove(&mut self, idx: Idx<T, U>) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        self.remove_with(idx, RemovalMode::Graceful)
+    }
+
+    // a pending removal can be made more urgent, but not less
+    pub fn remove_with(&mut self, idx: Idx<T, U>, mode: RemovalMode) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        self.check_poisoned()?;
+        let a = self.agents.get_mut(&idx).ok_or(Error::UnknownAgent(idx))?;
+        if !a.remove(mode) {
+            return Err(Error::AlreadyRemoving(idx))
+        }
+        if mode == RemovalMode::AtNextNode {
+            self.cancel_path(idx)?;
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
+    // the agent stays as if it were not removed, a path cancelled by `RemovalMode::AtNextNode` is planned again when it stops
+    pub fn cancel_removal(&mut self, idx: Idx<T, U>) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        self.check_poisoned()?;
+        let a = self.agents.get_mut(&idx).ok_or(Error::UnknownAgent(idx))?;
+        if !a.cancel_removal() {
+            return Err(Error::NotRemoving(idx))
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
+    // releases every seat of the agent including those waiting for their release times, and forgets it
+    fn leave(&mut self, idx: Idx<T, U>) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        let reserved = self.reservations.remove(&idx).unwrap_or_default();
+        if reserved.iter().any(|(_, t)| t.is_some()) {
+            self.durations.retain(|d| d.index() != idx);
         }
+        for (seat, _) in reserved {
+            self.map[seat.clone()].remove(idx).map_err(|error| Error::Seat { idx, seat, error })?;
+        }
+        let Some(a) = self.agents.remove(&idx) else { return Ok(()) };
+        self.allocator.release(idx.value());
+        self.events.push(StepEvent::Removed { idx, mode: a.removal().unwrap_or_default() });
+        self.dirty = true;
+        Ok(())
+    }
+
+    // the agent stops at the node it is moving to and the seats reserved beyond it are released
+    // it plans again there as usual since it keeps its destinations
+    pub fn cancel_path(&mut self, idx: Idx<T, U>) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        self.check_poisoned()?;
+        let a = self.agents.get_mut(&idx).ok_or(Error::UnknownAgent(idx))?;
+        let AgentState::Moving { nexts } = a.state() else { return Ok(()) };
+        let (node, arrival) = nexts[0].clone();
+        let stop_seats = self.map.seats(&node, a.kind()).collect::<HashSet<_>>();
+        a.stops_at_next();
 
-        return Idx::new(self.agents.keys().max().unwrap().value() + U::one())
+        // seats until the arrival are still needed by the current movement
+        let mut released = vec![];
+        let reserved = self.reservations.entry(idx).or_default();
+        reserved.retain_mut(|(seat, t)| {
+            if stop_seats.contains(seat) {
+                *t = None;
+                true
+            } else if t.is_some_and(|t| t <= arrival) {
+                true
+            } else {
+                released.push(seat.clone());
+                false
+            }
+        });
+        self.durations.retain(|d| d.index() != idx || d.time() <= arrival);
+        for seat in released {
+            if let Err(error) = self.map[seat.clone()].remove(idx) {
+                self.poisoned = true;
+                return Err(Error::Seat { idx, seat, error })
+            }
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
+    // cancels the path and reserves a new one departing from the node at which the agent stops, e.g. after its destinations are changed
+    // returns whether a new path with a movement is reserved, otherwise the agent plans when it stops
+    // the search is not reported in `events`
+    pub fn replan(&mut self, idx: Idx<T, U>) -> Result<bool, Error<M::Node, M::SeatIndex, T, U>> {
+        self.cancel_path(idx)?;
+        let a = &self.agents[&idx];
+        let AgentState::Moving { nexts } = a.state() else { return Ok(false) };
+        let (node, arrival) = nexts[0].clone();
+        // a destination at the stopping node is reached on the arrival
+        if a.removing() || a.next_destinations().is_none_or(|d| d.end_index(&node).is_some()) {
+            return Ok(false)
+        }
+        let planned = self.reserve_path(idx, node, arrival);
+        self.poisoned |= planned.is_err();
+        planned.map(|p| p == Some(true))
     }
 
-    pub fn remove(&mut self, idx: Idx<T, U>) -> bool {
-        let Some(a) = self.agents.get_mut(&idx) else { return false };
-        a.remove()
+    fn check_poisoned(&self) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        if self.poisoned {
+            return Err(Error::Poisoned)
+        }
+        Ok(())
     }
 
-    pub fn step(&mut self) {
+    pub fn step(&mut self) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        self.check_poisoned()?;
+        let advanced = self.advance();
+        self.poisoned |= advanced.is_err();
+        advanced?;
+
+        if self.validation {
+            self.validate().map_err(Error::Invalid)?;
+        }
+        Ok(())
+    }
+
+    // the state is partially updated when this fails
+    fn advance(&mut self) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        self.dirty = false;
+        self.last_step_time = self.time;
+        self.events.clear();
+
+        // agents removed immediately leave before any seat is released or reserved in this step
+        let immediate = self.queue
+            .iter()
+            .copied()
+            .filter(|idx| self.agents.get(idx).is_some_and(|a| a.removal() == Some(RemovalMode::Immediate)))
+            .collect::<Vec<_>>();
+        for idx in immediate {
+            self.leave(idx)?;
+        }
 
         // seat の解放
         while let Some(d) = self.durations.peek() {
@@ -87,8 +483,12 @@ impl<M: Map<U, T>, U: AgentIdxType + Ord, T> Simulator<M, U, T> where M::SeatInd
             }
 
             let d = self.durations.pop().unwrap();
-            let i = d.index();
-            self.map[d.seat()].remove(i);
+            let (idx, time) = (d.index(), d.time());
+            let seat = d.seat();
+            if let Some(r) = self.reservations.get_mut(&idx) {
+                r.retain(|(s, t)| !(*s == seat && *t == Some(time)));
+            }
+            self.map[seat.clone()].remove(idx).map_err(|error| Error::Seat { idx, seat, error })?;
         }
 
         for &idx in &self.queue {
@@ -97,17 +497,21 @@ impl<M: Map<U, T>, U: AgentIdxType + Ord, T> Simulator<M, U, T> where M::SeatInd
                 AgentState::NotPlaced => {
                     let can_place = self.map
                         .seats(a.current(), a.kind())
-                        .all(|n| self.map[n].is_empty_for(idx));
+                        .all(|n| self.map[n].is_empty_for_kind(idx, a.kind()));
                     if can_place {
-                        self.map
-                            .seats(a.current(), a.kind())
-                            .for_each(|n| self.map[n].add(idx));
+                        let mut reserved = vec![];
+                        for seat in self.map.seats(a.current(), a.kind()) {
+                            self.map[seat.clone()].add_with_kind(idx, a.kind()).map_err(|error| Error::Seat { idx, seat: seat.clone(), error })?;
+                            reserved.push((seat, None));
+                        }
+                        self.reservations.insert(idx, reserved);
                         a.place();
+                        self.events.push(StepEvent::Placed(idx));
                     }
                 },
-                AgentState::Moving { nexts } => {
-                    if nexts[0].1 <= self.time {
-                        a.arrives();
+                AgentState::Moving { nexts } if nexts[0].1 <= self.time => {
+                    if let Some(reached) = a.arrives() {
+                        self.events.push(StepEvent::Arrived { idx, reached });
                     }
                 },
                 _ => {},
@@ -118,15 +522,19 @@ impl<M: Map<U, T>, U: AgentIdxType + Ord, T> Simulator<M, U, T> where M::SeatInd
         while let Some(idx) = self.queue.pop_front() {
             let Some(a) = self.agents.get_mut(&idx) else { continue };
 
+            // an agent which is not placed yet holds no seats
+            if a.removing() && *a.state() == AgentState::NotPlaced {
+                self.leave(idx)?;
+                continue;
+            }
+
             let success = if let AgentState::Stop = a.state() {
                 if a.removing() {
-                    for s in self.map.seats(a.current(), a.kind()) {
-                        self.map[s].remove(idx);
-                    }
-                    self.agents.remove(&idx);
+                    // seats freed here are visible to the other agents from the next step
+                    self.leave(idx)?;
                     continue;
                 } else {
-                    self.set_nexts(idx);
+                    self.set_nexts(idx)?;
                 }
                 true
             } else {
@@ -142,77 +550,262 @@ impl<M: Map<U, T>, U: AgentIdxType + Ord, T> Simulator<M, U, T> where M::SeatInd
         self.queue.extend(idxs_fail);
         self.queue.extend(idxs_suc);
         
-        self.time = self.time + M::Cost::one();
+        self.time = match self.clock {
+            Clock::Tick => self.time + M::Cost::one(),
+            Clock::Event => self.next_event_time()
+                .map(|t| t.max(self.time))
+                .unwrap_or(self.time + M::Cost::one()),
+        };
+        Ok(())
+    }
+
+    pub fn step_to_next_event(&mut self) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        if let Some(t) = self.next_event_time() {
+            self.skip_idle_ticks(t);
+        }
+        self.step()
     }
 
-    fn set_nexts(&mut self, idx: Idx<T, U>) -> bool {
+    pub fn run_until(&mut self, time: M::Cost) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        while self.time < time {
+            let t = self.next_event_time().map_or(time, |t| t.min(time));
+            self.skip_idle_ticks(t);
+            if self.time >= time {
+                break;
+            }
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    pub fn run_for<F: FnMut(&Self)>(&mut self, n_steps: usize, mut f: F) -> Result<(), Error<M::Node, M::SeatIndex, T, U>> {
+        for _ in 0..n_steps {
+            self.step()?;
+            f(self);
+        }
+        Ok(())
+    }
+
+    pub fn run_until_idle(&mut self, max_steps: usize) -> Result<StopReason, Error<M::Node, M::SeatIndex, T, U>> {
+        self.run_until_idle_with(max_steps, |_| {})
+    }
+
+    pub fn run_until_idle_with<F: FnMut(&Self)>(&mut self, max_steps: usize, mut f: F) -> Result<StopReason, Error<M::Node, M::SeatIndex, T, U>> {
+        for n in 0.. {
+            if self.is_completed() {
+                return Ok(StopReason::Completed)
+            }
+            if !self.dirty && self.next_event_time().is_none() {
+                return Ok(StopReason::Deadlock)
+            }
+            if n == max_steps {
+                break;
+            }
+            self.step()?;
+            f(self);
+        }
+        Ok(StopReason::StepLimit)
+    }
+
+    // `step` fails with `Error::Invalid` if `validate` fails at its end, meant for debugging custom seats and maps
+    pub fn set_validation(&mut self, on: bool) { self.validation = on }
+
+    pub fn validate(&self) -> Result<(), Violation<M::SeatIndex, T, U>> {
+        let mut seats = HashSet::new();
+        for (&idx, a) in &self.agents {
+            let nodes = match a.state() {
+                AgentState::NotPlaced => continue,
+                AgentState::Stop => vec![a.current()],
+                AgentState::Moving { nexts } => {
+                    if nexts.iter().zip(nexts.iter().skip(1)).any(|((_, t0), (_, t1))| t0 > t1) {
+                        return Err(Violation::NonMonotonic { idx })
+                    }
+                    nexts.iter().map(|(n, _)| n).collect()
+                },
+            };
+            for seat in nodes.into_iter().flat_map(|n| self.map.seats(n, a.kind())) {
+                if !self.map[seat.clone()].is_held_by(idx) {
+                    return Err(Violation::NotHeld { idx, seat })
+                }
+                seats.insert(seat);
+            }
+        }
+
+        for (&idx, reserved) in &self.reservations {
+            if !self.agents.contains_key(&idx) {
+                if let Some((seat, _)) = reserved.first() {
+                    return Err(Violation::DeadHolder { idx, seat: seat.clone() })
+                }
+            }
+            for (seat, _) in reserved {
+                if !self.map[seat.clone()].is_held_by(idx) {
+                    return Err(Violation::NotHeld { idx, seat: seat.clone() })
+                }
+            }
+        }
+
+        for d in &self.durations {
+            let seat = d.clone().seat();
+            if !self.agents.contains_key(&d.index()) {
+                return Err(Violation::DeadHolder { idx: d.index(), seat })
+            }
+            seats.insert(seat);
+        }
+
+        for seat in seats {
+            let s = &self.map[seat.clone()];
+            let holders = self.agents.keys().copied().filter(|&idx| s.is_held_by(idx)).collect::<Vec<_>>();
+            if holders.len() > s.capacity() {
+                return Err(Violation::OverBooked { seat, holders })
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.agents
+            .values()
+            .all(|a| matches!(a.state(), AgentState::Stop) && a.all_destinations().is_empty() && !a.removing())
+    }
+
+    // advances `time` tick by tick without stepping while no step before `until` can change the state
+    fn skip_idle_ticks(&mut self, until: M::Cost) {
+        if self.dirty || self.clock == Clock::Event {
+            return;
+        }
+        while self.time < until {
+            self.time = self.time + M::Cost::one();
+        }
+    }
+
+    pub fn next_event_time(&self) -> Option<M::Cost> {
+        let release = self.durations.peek().map(|d| d.time());
+        let arrival = self.agents
+            .values()
+            .filter_map(|a| match a.state() {
+                AgentState::Moving { nexts } => Some(nexts[0].1),
+                _ => None,
+            })
+            .min();
+
+        match (release, arrival) {
+            (Some(r), Some(a)) => Some(r.min(a)),
+            (r, a) => r.or(a),
+        }
+    }
+
+    fn set_nexts(&mut self, idx: Idx<T, U>) -> Result<bool, Error<M::Node, M::SeatIndex, T, U>> {
         let Some(a) = self.agents.get_mut(&idx) else {
-            return false
+            return Ok(false)
         };
 
+        // destinations under the agent are reached without moving
+        while a.next_destinations().is_some_and(|d| d.end_index(a.current()).is_some()) {
+            a.destinations_mut().pop_front();
+            self.events.push(StepEvent::Arrived { idx, reached: true });
+        }
+
+        if a.next_destinations().is_none() {
+            return Ok(false);
+        }
+
+        let t0 = Instant::now();
+        let start = a.current().clone();
+        let planned = self.reserve_path(idx, start, self.time)?;
+        self.events.push(StepEvent::Planned { idx, success: planned == Some(true), elapsed: t0.elapsed() });
+        Ok(planned.is_some())
+    }
+
+    // searches a path from `start` departing at `time` and reserves its seats, the agent stands or arrives at `start` then
+    // returns whether the path has any movement, None if no path is found
+    fn reserve_path(&mut self, idx: Idx<T, U>, start: M::Node, time: M::Cost) -> Result<Option<bool>, Error<M::Node, M::SeatIndex, T, U>> {
+        let Some(a) = self.agents.get_mut(&idx) else {
+            return Ok(None)
+        };
         let Some(destinations) = a.next_destinations() else {
-            return false;
+            return Ok(None)
+        };
+
+        let heuristic = match self.planner {
+            Planner::AStar => self.map.heuristic(destinations),
+            Planner::Dijkstra => None,
         };
 
-        let path = if let Some(heuristic) = self.map.heuristic(destinations) {
+        let path = if let Some(heuristic) = heuristic {
             astar_for_next_reservation(
-                a.current().clone(),
+                start.clone(),
                 destinations,
                 |n| Successor::new(n.clone(), &self.map, a.kind()),
-                |s: &M::SeatIndex| self.map[s.clone()].is_empty_for(idx),
+                |s: &M::SeatIndex| self.map[s.clone()].is_empty_for_kind(idx, a.kind()),
                 self.max_reservation_time,
                 |n| heuristic.heuristic(n),
             )
         } else {
             dijkstra_for_next_reservation(
-                a.current().clone(),
+                start.clone(),
                 destinations,
                 |n| Successor::new(n.clone(), &self.map, a.kind()),
-                |s: &M::SeatIndex| self.map[s.clone()].is_empty_for(idx),
+                |s: &M::SeatIndex| self.map[s.clone()].is_empty_for_kind(idx, a.kind()),
                 self.max_reservation_time,
             )
         };
 
         let Some(path) = path else {
-            return false
+            return Ok(None)
         };
 
-        a.departs(path.iter().map(|(n, c, _)| (n.clone(), *c + self.time)));
+        // arrival times, an agent moves at most one node per step with Clock::Tick
+        let mut t = time;
+        let path = path.into_iter()
+            .map(|(n, c, i)| {
+                t = match self.clock {
+                    Clock::Tick => (time + c).max(t + M::Cost::one()),
+                    Clock::Event => time + c,
+                };
+                (n, t, i)
+            })
+            .collect::<Vec<_>>();
 
-        let len = path.len();
+        a.departs(path.iter().map(|(n, t, _)| (n.clone(), *t)));
 
+        // the seats of a node are held until the arrival at the next node
         let mut seats = HashMap::new();
-        let mut c0 = self.time;
-        for s in self.map.seats(a.current(), a.kind()) {
-            Self::add_seats(&mut seats, s, if len > 0 { Some(c0 + M::Cost::one()) } else { None });
+        for s in self.map.seats(&start, a.kind()) {
+            Self::add_seats(&mut seats, s, path.first().map(|(_, t, _)| *t));
         }
 
-        let mut n0 = a.current().clone();
-
-        for (j, (n, c, i)) in path.into_iter().enumerate() {
-            for (s, d) in self.map.seats_between(&n0, a.kind(), &i) {
-                Self::add_seats(&mut seats, s, Some(c0 + d));
-                n0 = n.clone();
+        let (mut n0, mut t0) = (start, time);
+        for (j, (n, t, i)) in path.iter().enumerate() {
+            for (s, d) in self.map.seats_between(&n0, a.kind(), i) {
+                Self::add_seats(&mut seats, s, Some(t0 + d));
             }
-            if j < len - 1 {
-                for s in self.map.seats(&n, a.kind()) {
-                    Self::add_seats(&mut seats, s, Some(self.time + M::Cost::one() + c));
-                }
-            } else {
-                for s in self.map.seats(&n, a.kind()) {
-                    Self::add_seats(&mut seats, s, None);
-                }
+            for s in self.map.seats(n, a.kind()) {
+                Self::add_seats(&mut seats, s, path.get(j + 1).map(|(_, t, _)| *t));
             }
-            c0 = c0 + c;
+            (n0, t0) = (n.clone(), *t);
         }
 
-        for (s, t) in seats {
-            self.map[s.clone()].add(idx);
+        let reserved = self.reservations.entry(idx).or_default();
+        for (seat, t) in seats {
+            self.map[seat.clone()].add_with_kind(idx, a.kind()).map_err(|error| Error::Seat { idx, seat: seat.clone(), error })?;
+            // a seat held already is kept until the new release time
+            match reserved.iter_mut().find(|(s, _)| *s == seat) {
+                Some((_, t0)) => {
+                    if let Some(t0) = std::mem::replace(t0, t) {
+                        Self::cancel_duration(&mut self.durations, idx, &seat, t0);
+                    }
+                },
+                None => reserved.push((seat.clone(), t)),
+            }
             if let Some(t) = t {
-                self.durations.push(Duration::new(t, idx, s));
+                self.durations.push(Duration::new(t, idx, seat));
             }
         }
-        true
+        Ok(Some(!path.is_empty()))
+    }
+
+    fn cancel_duration(durations: &mut BinaryHeap<Duration<M::Cost, M::SeatIndex, T, U>>, idx: Idx<T, U>, seat: &M::SeatIndex, time: M::Cost) {
+        durations.retain(|d| !(d.index() == idx && d.time() == time && d.clone().seat() == *seat));
     }
 
     fn add_seats(seats: &mut HashMap<M::SeatIndex, Option<M::Cost>>, s: M::SeatIndex, t: Option<M::Cost>) {
@@ -238,7 +831,7 @@ struct Successor<'a, M: Map<U, T>, U: AgentIdxType, T = ()> {
 
 impl<'a, M: Map<U, T>, U: AgentIdxType, T> Successor<'a, M, U, T> {
     fn new(node: M::Node, map: &'a M, kind: &'a T) -> Self {
-        let iter = map.successors(&node, &kind);
+        let iter = map.successors(&node, kind);
         Self { node, map, kind, iter, _phu: PhantomData }
     }
 }
@@ -249,12 +842,12 @@ impl<'a, M: Map<U, T>, U: AgentIdxType, T> Iterator for Successor<'a, M, U, T> {
     fn next(&mut self) -> Option<Self::Item> {
         self.iter
             .next()
-            .and_then(|(i, m, c)| Some((
+            .map(|(i, m, c)| (
                 m,
                 c,
-                SuccessorSeats::new(self.map, &self.node, &self.kind, &i),
+                SuccessorSeats::new(self.map, &self.node, self.kind, &i),
                 i,
-            )))
+            ))
     }
 }
 
@@ -268,7 +861,7 @@ impl<M: Map<U, T>, U: AgentIdxType, T> SuccessorSeats<M, U, T> {
         let s = map.seats_between(node, kind, index);
         let t = map
             .successor(node, kind, index)
-            .and_then(|ss| Some(map.seats(&ss, kind)));
+            .map(|ss| map.seats(&ss, kind));
         Self { s, t }
     }
 }
@@ -283,6 +876,382 @@ impl<M: Map<U, T>, U: AgentIdxType, T> Iterator for SuccessorSeats<M, U, T> {
         if let Some(t) = &mut self.t {
             return t.next()
         }
-        return None
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{agent_data::{AgentState, RemovalMode}, duration::Duration, grid::map::GridMap, index::{allocator::{Allocator, FreeListAllocator, GapAllocator, IdxAllocator, IdxSet}, index::Idx}, pathfind::common::MultipleEnds, seat::{Seat, SeatError}, Error};
+
+    use super::{Simulator, StepEvent, StopReason, Violation};
+
+    fn simulator() -> (Simulator<GridMap, u32>, Idx<(), u32>, Idx<(), u32>) {
+        let mut s = Simulator::new(0, GridMap::new(3, 2), 2);
+        let i0 = s.add((), (0, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(2, 0)])])).unwrap();
+        let i1 = s.add((), (0, 1), VecDeque::new()).unwrap();
+        (s, i0, i1)
+    }
+
+    #[test]
+    fn validate_test() {
+        let (mut s, i0, i1) = simulator();
+        s.step().unwrap();
+        assert_eq!(s.validate(), Ok(()));
+
+        // a seat on the reserved path is lost
+        s.map[(2, 0)].remove(i0).unwrap();
+        assert_eq!(s.validate(), Err(Violation::NotHeld { idx: i0, seat: (2, 0) }));
+        s.map[(2, 0)].add(i0).unwrap();
+
+        // the seat of a stopping agent is taken over
+        s.map[(0, 1)].remove(i1).unwrap();
+        s.map[(0, 1)].add(Idx::new(5)).unwrap();
+        assert_eq!(s.validate(), Err(Violation::NotHeld { idx: i1, seat: (0, 1) }));
+        s.map[(0, 1)].remove(Idx::new(5)).unwrap();
+        s.map[(0, 1)].add(i1).unwrap();
+
+        s.durations.push(Duration::new(10, Idx::new(5), (1, 1)));
+        assert_eq!(s.validate(), Err(Violation::DeadHolder { idx: Idx::new(5), seat: (1, 1) }));
+        s.durations.clear();
+
+        s.set_validation(true);
+        assert_eq!(s.run_until_idle(10).unwrap(), StopReason::Completed);
+        assert_eq!(s.validate(), Ok(()));
+    }
+
+    #[test]
+    fn reserved_seats_test() {
+        let (mut s, i0, i1) = simulator();
+        let reserved = |s: &Simulator<GridMap, u32>, idx| {
+            let mut r = s.reserved_seats(idx).unwrap().to_vec();
+            r.sort();
+            r
+        };
+        assert_eq!(reserved(&s, i0), vec![]);
+
+        s.step().unwrap();
+        assert_eq!(reserved(&s, i0), vec![((0, 0), Some(1)), ((1, 0), Some(2)), ((2, 0), None)]);
+        assert_eq!(reserved(&s, i1), vec![((0, 1), None)]);
+        s.step().unwrap();
+        assert_eq!(reserved(&s, i0), vec![((1, 0), Some(2)), ((2, 0), None)]);
+        assert_eq!(s.run_until_idle(10).unwrap(), StopReason::Completed);
+        assert_eq!(reserved(&s, i0), vec![((2, 0), None)]);
+
+        s.remove(i1).unwrap();
+        s.step().unwrap();
+        assert!(s.map().occupant((0, 1)).is_none());
+        assert_eq!(s.reserved_seats(i1), Err(Error::UnknownAgent(i1)));
+        assert!(!s.reservations.contains_key(&i1));
+    }
+
+    fn moving_simulator() -> (Simulator<GridMap, u32>, Idx<(), u32>) {
+        let mut s = Simulator::new(0, GridMap::new(5, 2), 10);
+        s.set_validation(true);
+        let idx = s.add((), (0, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(4, 0)])])).unwrap();
+        s.step().unwrap();
+        (s, idx)
+    }
+
+    fn sorted<N: Ord + Clone>(seats: &[N]) -> Vec<N> {
+        let mut seats = seats.to_vec();
+        seats.sort();
+        seats
+    }
+
+    #[test]
+    fn cancel_path_test() {
+        let (mut s, idx) = moving_simulator();
+        assert!(s.map().occupant((4, 0)).is_some());
+
+        s.cancel_path(idx).unwrap();
+        assert_eq!(s.agent(idx).unwrap().state(), &AgentState::Moving { nexts: VecDeque::from([((1, 0), 1)]) });
+        assert_eq!(sorted(s.reserved_seats(idx).unwrap()), vec![((0, 0), Some(1)), ((1, 0), None)]);
+        assert!((2..5).all(|x| s.map().occupant((x, 0)).is_none()));
+        assert_eq!(s.validate(), Ok(()));
+
+        // stops at (1, 0) and goes on to the destination
+        assert_eq!(s.run_until_idle(20).unwrap(), StopReason::Completed);
+        assert_eq!(s.agent(idx).unwrap().current(), &(4, 0));
+        assert_eq!(s.cancel_path(Idx::new(7)), Err(Error::UnknownAgent(Idx::new(7))));
+    }
+
+    #[test]
+    fn replan_test() {
+        let (mut s, idx) = moving_simulator();
+        *s.agent_destination_mut(idx).unwrap() = VecDeque::from([MultipleEnds::new_as_all_zero(vec![(1, 1)])]);
+
+        assert_eq!(s.replan(idx), Ok(true));
+        assert_eq!(s.agent(idx).unwrap().state(), &AgentState::Moving { nexts: VecDeque::from([((1, 0), 1), ((1, 1), 2)]) });
+        assert_eq!(sorted(s.reserved_seats(idx).unwrap()), vec![((0, 0), Some(1)), ((1, 0), Some(2)), ((1, 1), None)]);
+        assert!((2..5).all(|x| s.map().occupant((x, 0)).is_none()));
+
+        assert_eq!(s.run_until_idle(20).unwrap(), StopReason::Completed);
+        assert_eq!(s.agent(idx).unwrap().current(), &(1, 1));
+        assert_eq!(s.time(), 3);
+        assert_eq!(sorted(s.reserved_seats(idx).unwrap()), vec![((1, 1), None)]);
+
+        // a destination at the stopping node is reached on the arrival without a new path
+        let (mut s, idx) = moving_simulator();
+        *s.agent_destination_mut(idx).unwrap() = VecDeque::from([MultipleEnds::new_as_all_zero(vec![(1, 0)])]);
+        assert_eq!(s.replan(idx), Ok(false));
+        assert_eq!(s.run_until_idle(20).unwrap(), StopReason::Completed);
+        assert_eq!(s.agent(idx).unwrap().current(), &(1, 0));
+    }
+
+    #[test]
+    fn removal_mode_test() {
+        let is_free = |s: &Simulator<GridMap, u32>| (0..5).all(|x| (0..2).all(|y| s.map().occupant((x, y)).is_none()));
+
+        let (mut s, idx) = moving_simulator();
+        s.remove(idx).unwrap();
+        assert_eq!(s.run_until_idle(20).unwrap(), StopReason::Completed);
+        assert_eq!(s.time(), 5);
+        assert!(s.agents().is_empty() && is_free(&s));
+        assert_eq!(s.events(), &[StepEvent::Arrived { idx, reached: true }, StepEvent::Removed { idx, mode: RemovalMode::Graceful }]);
+
+        let (mut s, idx) = moving_simulator();
+        s.remove_with(idx, RemovalMode::AtNextNode).unwrap();
+        s.step().unwrap();
+        assert!(s.agents().is_empty() && is_free(&s));
+        assert_eq!(s.events(), &[StepEvent::Arrived { idx, reached: false }, StepEvent::Removed { idx, mode: RemovalMode::AtNextNode }]);
+
+        let (mut s, idx) = moving_simulator();
+        s.remove_with(idx, RemovalMode::Immediate).unwrap();
+        s.step().unwrap();
+        assert!(s.agents().is_empty() && s.durations.is_empty() && is_free(&s));
+        assert_eq!(s.events(), &[StepEvent::Removed { idx, mode: RemovalMode::Immediate }]);
+    }
+
+    #[test]
+    fn cancel_removal_test() {
+        let (mut s, idx) = moving_simulator();
+        assert_eq!(s.cancel_removal(idx), Err(Error::NotRemoving(idx)));
+
+        // only more urgent modes are accepted
+        s.remove(idx).unwrap();
+        assert_eq!(s.remove(idx), Err(Error::AlreadyRemoving(idx)));
+        s.remove_with(idx, RemovalMode::Immediate).unwrap();
+        assert_eq!(s.remove_with(idx, RemovalMode::AtNextNode), Err(Error::AlreadyRemoving(idx)));
+        assert_eq!(s.agent(idx).unwrap().removal(), Some(RemovalMode::Immediate));
+
+        s.cancel_removal(idx).unwrap();
+        s.step().unwrap();
+        assert!(!s.agent(idx).unwrap().removing());
+
+        // the cancelled path is planned again after the stop
+        s.remove_with(idx, RemovalMode::AtNextNode).unwrap();
+        s.cancel_removal(idx).unwrap();
+        assert_eq!(s.run_until_idle(20).unwrap(), StopReason::Completed);
+        assert_eq!(s.agent(idx).unwrap().current(), &(4, 0));
+    }
+
+    #[test]
+    fn validation_test() {
+        let (mut s, _, i1) = simulator();
+        s.set_validation(true);
+        s.step().unwrap();
+        s.map[(0, 1)].remove(i1).unwrap();
+        s.map[(0, 1)].add(Idx::new(5)).unwrap();
+        assert_eq!(s.step(), Err(Error::Invalid(Violation::NotHeld { idx: i1, seat: (0, 1) })));
+        assert_eq!(s.step().unwrap_err().to_string(), "invalid state after a step: seat (0, 1) is not held by agent 1");
+    }
+
+    #[test]
+    fn error_test() {
+        let (mut s, i0, _) = simulator();
+        s.step().unwrap();
+
+        let before = s.snapshot();
+        let time = s.time();
+
+        // the seat to be released after the departure is lost
+        s.map[(0, 0)].remove(i0).unwrap();
+        assert_eq!(s.step(), Err(Error::Seat { idx: i0, seat: (0, 0), error: SeatError::NotHeld }));
+        assert!(s.is_poisoned());
+        assert_eq!(s.time(), time);
+        assert_eq!(s.step(), Err(Error::Poisoned));
+        assert_eq!(s.remove(i0), Err(Error::Poisoned));
+        assert_eq!(s.cancel_path(i0), Err(Error::Poisoned));
+        assert_eq!(s.add((), (2, 1), VecDeque::new()), Err(Error::Poisoned));
+        assert_eq!(s.step().unwrap_err().to_string(), "the simulator was left partially updated by an earlier error");
+
+        s.restore(before);
+        assert!(!s.is_poisoned());
+        s.step().unwrap();
+        s.validate().unwrap();
+    }
+
+    #[test]
+    fn cancel_path_error_test() {
+        let (mut s, i0, _) = simulator();
+        s.step().unwrap();
+
+        // a seat reserved beyond the next node is lost
+        let (seat, _) = s.reserved_seats(i0).unwrap().iter().rev().find(|(n, _)| *n != (0, 0) && *n != (1, 0)).copied().unwrap();
+        s.map[seat].remove(i0).unwrap();
+        assert_eq!(s.cancel_path(i0), Err(Error::Seat { idx: i0, seat, error: SeatError::NotHeld }));
+        assert!(s.is_poisoned());
+        assert_eq!(s.replan(i0), Err(Error::Poisoned));
+    }
+
+    #[test]
+    fn add_test() {
+        // . # .
+        // . # .
+        let mut s = Simulator::<GridMap, u32>::new(0, GridMap::new(3, 2), 2);
+        s.map.set_blocked((1, 0), true);
+        s.map.set_blocked((1, 1), true);
+        let ends = |ns: Vec<(usize, usize)>| MultipleEnds::new_as_all_zero(ns);
+
+        assert_eq!(s.add((), (3, 0), VecDeque::new()), Err(Error::InvalidNode((3, 0))));
+        assert_eq!(s.add((), (1, 0), VecDeque::new()), Err(Error::InvalidNode((1, 0))));
+        assert_eq!(s.add((), (0, 0), VecDeque::from([ends(vec![(0, 1)]), ends(vec![])])), Err(Error::EmptyDestination { index: 1 }));
+        assert_eq!(s.add((), (0, 0), VecDeque::from([ends(vec![(0, 1), (1, 1)])])), Err(Error::InvalidDestination { index: 0, node: (1, 1) }));
+        assert_eq!(s.add((), (0, 0), VecDeque::from([ends(vec![(0, 1)]), ends(vec![(2, 0)])])), Err(Error::UnreachableDestination { index: 1 }));
+        assert!(s.agents().is_empty());
+
+        // one of the ends is enough
+        let i0 = s.add((), (0, 0), VecDeque::from([ends(vec![(2, 0), (0, 1)])])).unwrap();
+        assert_eq!(s.remove(i0), Ok(()));
+        assert_eq!(s.remove(i0), Err(Error::AlreadyRemoving(i0)));
+        assert_eq!(s.remove(Idx::new(5)), Err(Error::UnknownAgent(Idx::new(5))));
+        assert!(matches!(s.movement_of(Idx::new(5), 0), Err(Error::UnknownAgent(_))));
+    }
+
+    #[test]
+    fn stale_idx_test() {
+        let (mut s, i0, i1) = simulator();
+        s.remove(i1).unwrap();
+        s.step().unwrap();
+        assert!(s.agent(i1).is_none());
+
+        // the index of the removed agent is not handed out again
+        let i2 = s.add((), (0, 1), VecDeque::new()).unwrap();
+        assert!(i2 != i0 && i2 != i1);
+        assert!(s.agent(i1).is_none());
+
+        // whereas the gap allocator fills the gap below the smallest index
+        s.set_allocator(GapAllocator);
+        s.remove(i0).unwrap();
+        s.run_until_idle(10).unwrap();
+        assert_eq!(s.add((), (0, 0), VecDeque::new()), Ok(Idx::new(i2.value() - 1)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn allocator_serde_test() {
+        let (mut s, _, i1) = simulator();
+        s.remove(i1).unwrap();
+        s.step().unwrap();
+
+        // the counter is kept, so the index of the removed agent is not handed out after the restore
+        let json = serde_json::to_string(&s.snapshot()).unwrap();
+        let mut restored = s.clone();
+        restored.restore(serde_json::from_str(&json).unwrap());
+        assert_eq!(restored.add((), (0, 1), VecDeque::new()), Ok(Idx::new(i1.value() + 1)));
+
+        // so is the kind of the allocator
+        s.set_allocator(FreeListAllocator::default());
+        let i2 = s.add((), (0, 1), VecDeque::new()).unwrap();
+        assert_eq!(i2, i1);
+        s.remove(i2).unwrap();
+        s.step().unwrap();
+        let json = serde_json::to_string(&s.snapshot()).unwrap();
+        let mut restored = s.clone();
+        restored.restore(serde_json::from_str(&json).unwrap());
+        assert_eq!(restored.add((), (0, 1), VecDeque::new()), Ok(i2));
+    }
+
+    // hands out even indices only
+    #[derive(Clone)]
+    struct EvenAllocator;
+
+    impl IdxAllocator<u32> for EvenAllocator {
+        fn allocate(&mut self, living: &dyn IdxSet<u32>) -> Option<u32> {
+            (0..u32::MAX).step_by(2).find(|&i| !living.contains(i))
+        }
+
+        fn clone_box(&self) -> Box<dyn IdxAllocator<u32>> { Box::new(self.clone()) }
+    }
+
+    #[test]
+    fn custom_allocator_test() {
+        let mut s = Simulator::<GridMap, u32>::new(0, GridMap::new(4, 1), 2);
+        s.set_allocator(Allocator::Custom(Box::new(EvenAllocator)));
+        assert_eq!(s.add((), (0, 0), VecDeque::new()), Ok(Idx::new(0)));
+        assert_eq!(s.add((), (1, 0), VecDeque::new()), Ok(Idx::new(2)));
+
+        // a snapshot keeps the custom allocator
+        let snapshot = s.snapshot();
+        assert_eq!(s.add((), (2, 0), VecDeque::new()), Ok(Idx::new(4)));
+        s.restore(snapshot);
+        assert_eq!(s.add((), (2, 0), VecDeque::new()), Ok(Idx::new(4)));
+
+        // whereas a serialized one counts up from the largest index
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&s.snapshot()).unwrap();
+            s.restore(serde_json::from_str(&json).unwrap());
+            assert_eq!(s.add((), (3, 0), VecDeque::new()), Ok(Idx::new(5)));
+        }
+    }
+
+    #[test]
+    fn add_with_idx_test() {
+        let mut s = Simulator::<GridMap<u8>, u8>::new(0, GridMap::new(16, 16), 2);
+        s.set_allocator(FreeListAllocator::default());
+        let ends = |n| VecDeque::from([MultipleEnds::new_as_all_zero(vec![n])]);
+
+        assert_eq!(s.add_with_idx(Idx::new(1), (), (0, 0), ends((1, 0))), Ok(()));
+        assert_eq!(s.add_with_idx(Idx::new(1), (), (0, 1), ends((1, 1))), Err(Error::IdxInUse(Idx::new(1))));
+        assert_eq!(s.add_with_idx(Idx::new(u8::MAX), (), (0, 1), ends((1, 1))), Err(Error::NullIdx));
+        // the allocator skips the index taken by the caller
+        assert_eq!(s.add((), (0, 1), ends((1, 1))), Ok(Idx::new(0)));
+        assert_eq!(s.add((), (0, 2), ends((1, 2))), Ok(Idx::new(2)));
+
+        // every index below the null value is used
+        for k in 3..u8::MAX {
+            let n = (k as usize % 16, k as usize / 16);
+            assert_eq!(s.add((), n, VecDeque::new()), Ok(Idx::new(k)));
+        }
+        assert_eq!(s.add((), (15, 15), VecDeque::new()), Err(Error::IdxExhausted));
+    }
+
+    #[test]
+    fn release_time_test() {
+        let mut s: Simulator<GridMap, u32> = Simulator::new(0, GridMap::new(4, 1), 10);
+        s.add((), (0, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(3, 0)])])).unwrap();
+        s.step().unwrap();
+
+        let mut held: Vec<_> = s.durations.iter().map(|d| (d.clone().seat(), d.time())).collect();
+        held.sort();
+        assert_eq!(held, vec![((0, 0), 1), ((1, 0), 2), ((2, 0), 3)]);
+    }
+
+    #[test]
+    fn destination_under_agent_test() {
+        let mut s: Simulator<GridMap, u32> = Simulator::new(0, GridMap::new(3, 1), 10);
+        let i = s.add((), (0, 0), VecDeque::from([
+            MultipleEnds::new_as_all_zero(vec![(0, 0)]),
+            MultipleEnds::new_as_all_zero(vec![(2, 0)]),
+        ])).unwrap();
+
+        assert_eq!(s.run_until_idle(10), Ok(StopReason::Completed));
+        assert_eq!(s.agent(i).unwrap().current(), &(2, 0));
+    }
+
+    #[test]
+    fn remove_not_placed_test() {
+        let (mut s, _, i1) = simulator();
+        // the seat is taken by i1, so this agent is never placed
+        let i2 = s.add((), (0, 1), VecDeque::new()).unwrap();
+        s.step().unwrap();
+        s.remove(i2).unwrap();
+        s.step().unwrap();
+        assert!(s.agent(i2).is_none());
+        assert!(s.agent(i1).is_some());
     }
 }
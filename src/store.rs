@@ -0,0 +1,257 @@
+use std::{fmt::{Debug, Formatter}, hash::{Hash, Hasher}, marker::PhantomData, ops::{Index, IndexMut}};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{index::{index::Idx, nullable::Nullable}, seat::AgentIdxType};
+
+// seat indices numbered densely from 0 within the shape of a map
+pub trait DenseSeatIndex: Sized {
+    // e.g. the number of seats, or (nx, ny) of a grid
+    type Shape: Copy + Eq + Hash + Debug;
+
+    fn n_seats(shape: &Self::Shape) -> usize;
+    fn to_dense(&self, shape: &Self::Shape) -> usize;
+    fn from_dense(k: usize, shape: &Self::Shape) -> Self;
+}
+
+impl DenseSeatIndex for usize {
+    type Shape = usize;
+
+    fn n_seats(shape: &usize) -> usize { *shape }
+    fn to_dense(&self, _: &usize) -> usize { *self }
+    fn from_dense(k: usize, _: &usize) -> Self { k }
+}
+
+// column major like `GridMap`
+impl DenseSeatIndex for (usize, usize) {
+    type Shape = (usize, usize);
+
+    fn n_seats(&(nx, ny): &(usize, usize)) -> usize { nx * ny }
+
+    fn to_dense(&self, &(nx, ny): &(usize, usize)) -> usize {
+        assert!(self.0 < nx && self.1 < ny, "seat {:?} is out of the shape {:?}", self, (nx, ny));
+        self.0 * ny + self.1
+    }
+
+    fn from_dense(k: usize, &(_, ny): &(usize, usize)) -> Self { (k / ny, k % ny) }
+}
+
+// occupancies of the seats of a map held by at most one agent each, a map indexes into it by its seat index
+// `Nullable` keeps a seat as small as the index, half of `Option<Idx>` for u32
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(
+    serialize = "I::Shape: Serialize, U: Serialize",
+    deserialize = "I::Shape: Deserialize<'de>, U: Deserialize<'de>",
+)))]
+pub struct DenseSeatStore<I: DenseSeatIndex, T, U: AgentIdxType> {
+    shape: I::Shape,
+    seats: Vec<Nullable<T, U>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    _p: PhantomData<I>,
+}
+
+impl<I: DenseSeatIndex, T, U: AgentIdxType> DenseSeatStore<I, T, U> {
+    pub fn new(shape: I::Shape) -> Self {
+        Self { shape, seats: vec![Nullable::new_null(); I::n_seats(&shape)], _p: PhantomData }
+    }
+
+    pub fn shape(&self) -> &I::Shape { &self.shape }
+    pub fn len(&self) -> usize { self.seats.len() }
+    pub fn is_empty(&self) -> bool { self.seats.is_empty() }
+
+    pub fn get(&self, i: &I) -> Option<Idx<T, U>> {
+        self.seats[i.to_dense(&self.shape)].value()
+    }
+
+    // (seat index, occupant) of the occupied seats in the dense order
+    pub fn occupied(&self) -> impl Iterator<Item = (I, Idx<T, U>)> + '_ {
+        self.seats.iter().enumerate().filter_map(|(k, s)| s.value().map(|i| (I::from_dense(k, &self.shape), i)))
+    }
+
+    pub fn occupants(&self) -> impl Iterator<Item = Idx<T, U>> + '_ {
+        self.seats.iter().filter_map(|s| s.value())
+    }
+
+    pub fn n_occupied(&self) -> usize {
+        self.seats.iter().filter(|s| !s.is_null()).count()
+    }
+
+    pub fn clear(&mut self) {
+        self.seats.fill(Nullable::new_null());
+    }
+}
+
+impl<I: DenseSeatIndex, T, U: AgentIdxType> Index<I> for DenseSeatStore<I, T, U> {
+    type Output = Nullable<T, U>;
+
+    fn index(&self, i: I) -> &Self::Output {
+        &self.seats[i.to_dense(&self.shape)]
+    }
+}
+
+impl<I: DenseSeatIndex, T, U: AgentIdxType> IndexMut<I> for DenseSeatStore<I, T, U> {
+    fn index_mut(&mut self, i: I) -> &mut Self::Output {
+        &mut self.seats[i.to_dense(&self.shape)]
+    }
+}
+
+impl<I: DenseSeatIndex, T, U: AgentIdxType> Clone for DenseSeatStore<I, T, U> {
+    fn clone(&self) -> Self {
+        Self { shape: self.shape, seats: self.seats.clone(), _p: PhantomData }
+    }
+}
+
+impl<I: DenseSeatIndex, T, U: AgentIdxType> PartialEq for DenseSeatStore<I, T, U> {
+    fn eq(&self, other: &Self) -> bool {
+        self.shape == other.shape && self.seats == other.seats
+    }
+}
+
+impl<I: DenseSeatIndex, T, U: AgentIdxType> Eq for DenseSeatStore<I, T, U> {}
+
+impl<I: DenseSeatIndex, T, U: AgentIdxType + Hash> Hash for DenseSeatStore<I, T, U> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.shape.hash(state);
+        self.seats.hash(state);
+    }
+}
+
+// only the occupied seats are listed
+impl<I: DenseSeatIndex + Debug, T, U: AgentIdxType + Debug> Debug for DenseSeatStore<I, T, U> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DenseSeatStore")
+            .field("shape", &self.shape)
+            .field("occupied", &DebugOccupied(self))
+            .finish()
+    }
+}
+
+struct DebugOccupied<'a, I: DenseSeatIndex, T, U: AgentIdxType>(&'a DenseSeatStore<I, T, U>);
+
+impl<I: DenseSeatIndex + Debug, T, U: AgentIdxType + Debug> Debug for DebugOccupied<'_, I, T, U> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.0.occupied()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::{HashSet, VecDeque}, iter::{once, Once}, ops::{Index, IndexMut, Range}, vec::IntoIter};
+
+    use crate::{index::{index::Idx, nullable::Nullable}, map::{DummyHeuristic, Map}, pathfind::common::MultipleEnds, seat::{Seat, SeatError}, simulator::{Simulator, StopReason}};
+
+    use super::{DenseSeatIndex, DenseSeatStore};
+
+    #[test]
+    fn dense_index_test() {
+        let shape = (3, 4);
+        assert_eq!(<(usize, usize)>::n_seats(&shape), 12);
+        for k in 0..12 {
+            assert_eq!(<(usize, usize)>::from_dense(k, &shape).to_dense(&shape), k);
+        }
+        assert_eq!((2, 1).to_dense(&shape), 9);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of the shape")]
+    fn out_of_shape_test() {
+        DenseSeatStore::<(usize, usize), (), u32>::new((3, 4)).get(&(0, 4));
+    }
+
+    #[test]
+    fn store_test() {
+        let (i0, i1) = (Idx::new(0), Idx::new(1));
+        let mut store = DenseSeatStore::<(usize, usize), (), u32>::new((3, 2));
+        assert_eq!(store.len(), 6);
+        assert_eq!(store.n_occupied(), 0);
+
+        assert_eq!(store[(2, 1)].add(i0), Ok(()));
+        assert_eq!(store[(0, 1)].add(i1), Ok(()));
+        assert_eq!(store[(2, 1)].add(i1), Err(SeatError::AlreadyOccupied));
+        assert_eq!(store.get(&(2, 1)), Some(i0));
+        assert_eq!(store.occupied().collect::<Vec<_>>(), vec![((0, 1), i1), ((2, 1), i0)]);
+        assert_eq!(store.occupants().collect::<Vec<_>>(), vec![i1, i0]);
+        assert_eq!(format!("{:?}", store), "DenseSeatStore { shape: (3, 2), occupied: {(0, 1): Idx { () 1 }, (2, 1): Idx { () 0 }} }");
+
+        let mut other = store.clone();
+        assert_eq!(other, store);
+        assert_eq!(other[(0, 1)].remove(i1), Ok(()));
+        assert_ne!(other, store);
+        assert_eq!(HashSet::from([store.clone(), other.clone(), store.clone()]).len(), 2);
+
+        store.clear();
+        assert_eq!(store.n_occupied(), 0);
+        assert_eq!(store, DenseSeatStore::new((3, 2)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_test() {
+        let mut store = DenseSeatStore::<usize, (), u32>::new(3);
+        store[1].add(Idx::new(5)).unwrap();
+
+        let json = serde_json::to_string(&store).unwrap();
+        assert_eq!(json, r#"{"shape":3,"seats":[null,5,null]}"#);
+        assert_eq!(serde_json::from_str::<DenseSeatStore<usize, (), u32>>(&json).unwrap(), store);
+    }
+
+    // a line on which agents move one seat at a time
+    struct LineMap {
+        store: DenseSeatStore<usize, (), u32>,
+    }
+
+    impl Map<u32> for LineMap {
+        type Cost = u32;
+        type I = usize;
+        type SeatIndex = usize;
+        type Seat = Nullable<(), u32>;
+        type Node = usize;
+
+        type SIter = Range<usize>;
+        type SCIter = IntoIter<(Self::I, Self::Node, Self::Cost)>;
+        type SBIter = Once<(Self::SeatIndex, Self::Cost)>;
+
+        type FH = DummyHeuristic;
+
+        fn seats(&self, &n: &Self::Node, _: &()) -> Self::SIter {
+            n..n + 1
+        }
+
+        fn successors(&self, n: &Self::Node, t: &()) -> Self::SCIter {
+            (0..2).filter_map(|i| self.successor(n, t, &i).map(|m| (i, m, 1))).collect::<Vec<_>>().into_iter()
+        }
+
+        fn successor(&self, &n: &Self::Node, _: &(), &i: &Self::I) -> Option<Self::Node> {
+            match i {
+                0 if n + 1 < self.store.len() => Some(n + 1),
+                1 if n > 0 => Some(n - 1),
+                _ => None,
+            }
+        }
+
+        fn seats_between(&self, &n: &Self::Node, _: &(), _: &Self::I) -> Self::SBIter {
+            once((n, 1))
+        }
+    }
+
+    impl Index<usize> for LineMap {
+        type Output = Nullable<(), u32>;
+        fn index(&self, i: usize) -> &Self::Output { &self.store[i] }
+    }
+
+    impl IndexMut<usize> for LineMap {
+        fn index_mut(&mut self, i: usize) -> &mut Self::Output { &mut self.store[i] }
+    }
+
+    #[test]
+    fn simulator_test() {
+        let mut s = Simulator::new(0, LineMap { store: DenseSeatStore::new(6) }, 3);
+        s.set_validation(true);
+        let i0 = s.add((), 0, VecDeque::from([MultipleEnds::new_as_all_zero(vec![4])])).unwrap();
+        let i1 = s.add((), 1, VecDeque::from([MultipleEnds::new_as_all_zero(vec![5])])).unwrap();
+
+        assert_eq!(s.run_until_idle(100).unwrap(), StopReason::Completed);
+        assert_eq!(s.map().store.occupied().collect::<Vec<_>>(), vec![(4, i0), (5, i1)]);
+    }
+}
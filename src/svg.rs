@@ -0,0 +1,232 @@
+use std::fmt::{Display, Write};
+
+use crate::trajectory::{Trajectory, TrajectoryAgent};
+
+const SEAT_COLOR: &str = "#d0d0d0";
+
+// static picture of frame `t`
+pub fn render_frame<U: Ord + Display, C>(trajectory: &Trajectory<U, C>, t: usize) -> String {
+    let mut svg = header(trajectory);
+
+    for seat in &trajectory.seats {
+        let fill = match seat.agent.get(t) {
+            Some(Some(i)) => color_of(trajectory, i),
+            _ => SEAT_COLOR.to_string(),
+        };
+        writeln!(svg, r#"<rect x="{}" y="{}" width="1" height="1" fill="{}" fill-opacity="0.4" stroke="white" stroke-width="0.02"/>"#, seat.x - 0.5, seat.y - 0.5, fill).unwrap();
+    }
+
+    for (k, (i, frames)) in trajectory.agents.iter().enumerate() {
+        let Some(a) = frames.get(&t) else { continue };
+        writeln!(svg, r#"<g id="agent-{}">"#, i).unwrap();
+        agent(&mut svg, a, &color(k));
+        svg.push_str("</g>\n");
+    }
+
+    footer(&mut svg);
+    svg
+}
+
+// all frames in one SVG animated with SMIL, each frame is shown for `frame_duration` seconds
+pub fn render_animation<U: Ord + Display, C>(trajectory: &Trajectory<U, C>, frame_duration: f32) -> String {
+    let n = n_frames(trajectory);
+    let dur = frame_duration * n as f32;
+    let mut svg = header(trajectory);
+
+    for seat in &trajectory.seats {
+        let fills = changes((0..n).map(|t| match seat.agent.get(t) {
+            Some(Some(i)) => color_of(trajectory, i),
+            _ => SEAT_COLOR.to_string(),
+        }));
+        let fill = fills.first().map_or(SEAT_COLOR, |(_, v)| v.as_str());
+        writeln!(svg, r#"<rect x="{}" y="{}" width="1" height="1" fill="{}" fill-opacity="0.4" stroke="white" stroke-width="0.02">"#, seat.x - 0.5, seat.y - 0.5, fill).unwrap();
+        animate(&mut svg, "fill", &fills, n, dur);
+        svg.push_str("</rect>\n");
+    }
+
+    for (k, (i, frames)) in trajectory.agents.iter().enumerate() {
+        writeln!(svg, r#"<g id="agent-{}">"#, i).unwrap();
+
+        // consecutive frames with the same picture share one group
+        let mut runs: Vec<(usize, usize, &TrajectoryAgent)> = vec![];
+        for (&t, a) in frames {
+            match runs.last_mut() {
+                Some((_, end, b)) if *end == t && *b == a => *end = t + 1,
+                _ => runs.push((t, t + 1, a)),
+            }
+        }
+
+        for (begin, end, a) in runs {
+            let visibilities = changes((0..n).map(|t| if begin <= t && t < end { "visible" } else { "hidden" }.to_string()));
+            writeln!(svg, r#"<g visibility="{}">"#, visibilities.first().map_or("hidden", |(_, v)| v.as_str())).unwrap();
+            animate(&mut svg, "visibility", &visibilities, n, dur);
+            agent(&mut svg, a, &color(k));
+            svg.push_str("</g>\n");
+        }
+
+        svg.push_str("</g>\n");
+    }
+
+    footer(&mut svg);
+    svg
+}
+
+fn n_frames<U: Ord, C>(trajectory: &Trajectory<U, C>) -> usize {
+    let seats = trajectory.seats.iter().map(|s| s.agent.len()).max().unwrap_or(0);
+    let agents = trajectory.agents.values().filter_map(|f| f.keys().max()).map(|t| t + 1).max().unwrap_or(0);
+    seats.max(agents)
+}
+
+fn header<U: Ord, C>(trajectory: &Trajectory<U, C>) -> String {
+    let (mut x0, mut y0, mut x1, mut y1) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for s in &trajectory.seats {
+        (x0, y0, x1, y1) = (x0.min(s.x), y0.min(s.y), x1.max(s.x), y1.max(s.y));
+    }
+    if trajectory.seats.is_empty() {
+        (x0, y0, x1, y1) = (0., 0., 0., 0.);
+    }
+
+    let mut svg = String::new();
+    // y axis points upward as in the viewer
+    writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" width="{}" height="{}">"#,
+        x0 - 1., -y1 - 1., x1 - x0 + 2., y1 - y0 + 2., (x1 - x0 + 2.) * 40., (y1 - y0 + 2.) * 40.).unwrap();
+    svg.push_str("<g transform=\"scale(1 -1)\">\n");
+
+    for s in &trajectory.seats {
+        for &(j, _) in &s.nexts {
+            let Some(n) = trajectory.seats.get(j) else { continue };
+            writeln!(svg, r##"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="#a0a0a0" stroke-width="0.03"/>"##, s.x, s.y, n.x, n.y).unwrap();
+        }
+    }
+    svg
+}
+
+fn footer(svg: &mut String) {
+    svg.push_str("</g>\n</svg>\n");
+}
+
+fn agent(svg: &mut String, a: &TrajectoryAgent, color: &str) {
+    let dash = if a.state == "n" { r#" stroke-dasharray="0.1 0.1""# } else { "" };
+    for polygon in a.shape.split(|p| p.is_none()).filter(|p| !p.is_empty()) {
+        let points = polygon.iter().flatten().map(|(x, y)| format!("{},{}", x, y)).collect::<Vec<_>>().join(" ");
+        writeln!(svg, r#"<polygon points="{}" fill="{}" fill-opacity="0.6" stroke="{}" stroke-width="0.05"{}/>"#, points, color, color, dash).unwrap();
+    }
+    for (x, y) in &a.destinations {
+        writeln!(svg, r#"<circle cx="{}" cy="{}" r="0.15" fill="none" stroke="{}" stroke-width="0.05"/>"#, x, y, color).unwrap();
+    }
+}
+
+// the frames where the value changes with the new value, the first one is the initial value
+fn changes<I: Iterator<Item = String>>(values: I) -> Vec<(usize, String)> {
+    let mut changes: Vec<(usize, String)> = vec![];
+    for (t, v) in values.enumerate() {
+        if changes.last().is_none_or(|(_, w)| *w != v) {
+            changes.push((t, v));
+        }
+    }
+    changes
+}
+
+// discrete animation of `changes`, nothing is written if the initial value of the element is kept throughout
+fn animate(svg: &mut String, attribute: &str, changes: &[(usize, String)], n: usize, dur: f32) {
+    if changes.len() <= 1 {
+        return
+    }
+
+    let values = changes.iter().map(|(_, v)| v.as_str()).collect::<Vec<_>>().join(";");
+    let key_times = changes.iter().map(|(t, _)| format!("{}", *t as f32 / n as f32)).collect::<Vec<_>>().join(";");
+    writeln!(svg, r#"<animate attributeName="{}" calcMode="discrete" dur="{}s" repeatCount="indefinite" values="{}" keyTimes="{}"/>"#, attribute, dur, values, key_times).unwrap();
+}
+
+fn color(k: usize) -> String {
+    format!("hsl({}, 70%, 45%)", (k * 137) % 360)
+}
+
+fn color_of<U: Ord, C>(trajectory: &Trajectory<U, C>, i: &U) -> String {
+    trajectory.agents.keys().position(|j| j == i).map_or(SEAT_COLOR.to_string(), color)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::trajectory::{Trajectory, TrajectoryAgent, TrajectorySeat};
+
+    use super::{render_animation, render_frame};
+
+    fn trajectory() -> Trajectory<u32, u32> {
+        let square = |x: f32| [(x - 0.4, -0.4), (x + 0.4, -0.4), (x + 0.4, 0.4), (x - 0.4, 0.4), (x - 0.4, -0.4)].map(Some).into_iter().chain([None]).collect::<Vec<_>>();
+        let agent = |x: f32, state: &str| TrajectoryAgent { shape: square(x), state: state.to_string(), destinations: vec![(2., 0.)] };
+
+        Trajectory {
+            seats: vec![
+                TrajectorySeat { x: 0., y: 0., nexts: vec![(1, 1)], agent: vec![Some(0), None, None] },
+                TrajectorySeat { x: 1., y: 0., nexts: vec![(2, 1)], agent: vec![None, Some(0), None] },
+                TrajectorySeat { x: 2., y: 0., nexts: vec![], agent: vec![None, None, Some(0)] },
+            ],
+            agents: BTreeMap::from([(0, BTreeMap::from([(0, agent(0., "n")), (1, agent(1., "m")), (2, agent(2., "s"))]))]),
+        }
+    }
+
+    #[test]
+    fn frame_test() {
+        let svg = render_frame(&trajectory(), 1);
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<rect").count(), 3);
+        assert_eq!(svg.matches("<line").count(), 2);
+        assert_eq!(svg.matches("<polygon").count(), 1);
+        assert_eq!(svg.matches("<circle").count(), 1);
+        assert!(svg.contains("0.6,-0.4"));
+        assert!(!svg.contains("stroke-dasharray"));
+    }
+
+    #[test]
+    fn animation_test() {
+        let svg = render_animation(&trajectory(), 0.5);
+
+        assert_eq!(svg.matches("<polygon").count(), 3);
+        assert_eq!(svg.matches("stroke-dasharray").count(), 1);
+        // every seat changes its occupant once or twice
+        assert_eq!(svg.matches("attributeName=\"fill\"").count(), 3);
+        assert_eq!(svg.matches("attributeName=\"visibility\"").count(), 3);
+        assert!(svg.contains(r#"dur="1.5s""#));
+        assert!(svg.contains(r#"values="hidden;visible;hidden" keyTimes="0;0.33333334;0.6666667""#));
+        // the first picture is shown from the beginning
+        assert!(svg.contains(r#"<g visibility="visible">"#));
+    }
+
+    #[test]
+    fn parked_agent_test() {
+        let mut t = trajectory();
+        let a = t.agents[&0][&0].clone();
+        t.agents.insert(0, (0..3).map(|k| (k, a.clone())).collect());
+        t.seats[0].agent = vec![Some(0); 3];
+        t.seats[1].agent = vec![None; 3];
+        t.seats[2].agent = vec![None; 3];
+        let svg = render_animation(&t, 0.5);
+
+        // the agent is drawn once and shown throughout without any animation
+        assert_eq!(svg.matches("<polygon").count(), 1);
+        assert_eq!(svg.matches("<animate").count(), 0);
+        assert!(svg.contains(r#"<g visibility="visible">"#));
+        assert!(!svg.contains(r#"visibility="hidden""#));
+        // the seat under it keeps the color of the agent
+        assert_eq!(svg.matches(r#"fill="hsl(0, 70%, 45%)" fill-opacity="0.4""#).count(), 1);
+    }
+
+    #[test]
+    fn single_frame_test() {
+        let mut t = trajectory();
+        t.agents.get_mut(&0).unwrap().retain(|&k, _| k == 0);
+        for seat in &mut t.seats {
+            seat.agent.truncate(1);
+        }
+        let svg = render_animation(&t, 0.5);
+
+        assert_eq!(svg.matches("<animate").count(), 0);
+        assert_eq!(svg.matches(r#"<g visibility="visible">"#).count(), 1);
+        assert_eq!(svg.matches(r#"fill="hsl(0, 70%, 45%)" fill-opacity="0.4""#).count(), 1);
+    }
+}
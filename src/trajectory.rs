@@ -0,0 +1,117 @@
+use std::{collections::{BTreeMap, HashMap}, hash::Hash, io::Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{agent_data::AgentState, map::Map, seat::AgentIdxType, simulator::Simulator};
+
+// the format read by tests/visual/viewer/viewer.html
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trajectory<U: Ord, C> {
+    pub seats: Vec<TrajectorySeat<U, C>>,
+    // agent -> frame -> shape and state
+    pub agents: BTreeMap<U, BTreeMap<usize, TrajectoryAgent>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrajectorySeat<U, C> {
+    pub x: f32,
+    pub y: f32,
+    // (index in `seats`, cost), drawn as arrows
+    pub nexts: Vec<(usize, C)>,
+    // occupant at each frame
+    pub agent: Vec<Option<U>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrajectoryAgent {
+    // polygons separated by None
+    pub shape: Vec<Option<(f32, f32)>>,
+    // "n": not placed, "s": stop, "m": moving
+    pub state: String,
+    // seats of the next destinations
+    #[serde(default)]
+    pub destinations: Vec<(f32, f32)>,
+}
+
+pub struct TrajectoryRecorder<M: Map<U, T>, U: AgentIdxType + Ord, F: FnMut(&M::SeatIndex) -> (f32, f32), T = ()> {
+    seats: Vec<M::SeatIndex>,
+    coordinate: F,
+    trajectory: Trajectory<U, M::Cost>,
+    n_frames: usize,
+    // side of the square drawn on each seat of an agent
+    agent_size: f32,
+}
+
+impl<M: Map<U, T>, U: AgentIdxType + Ord, F: FnMut(&M::SeatIndex) -> (f32, f32), T> TrajectoryRecorder<M, U, F, T> where M::SeatIndex: Hash {
+    pub fn new(seats: Vec<M::SeatIndex>, coordinate: F) -> Self {
+        Self::new_with_nexts(seats.into_iter().map(|s| (s, vec![])).collect(), coordinate)
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn new_with_nexts(seats: Vec<(M::SeatIndex, Vec<(usize, M::Cost)>)>, mut coordinate: F) -> Self {
+        let (seats, trajectory_seats) = seats.into_iter()
+            .map(|(s, nexts)| {
+                let (x, y) = coordinate(&s);
+                (s, TrajectorySeat { x, y, nexts, agent: vec![] })
+            })
+            .unzip();
+        Self { seats, coordinate, trajectory: Trajectory { seats: trajectory_seats, agents: BTreeMap::new() }, n_frames: 0, agent_size: 0.8 }
+    }
+
+    pub fn n_frames(&self) -> usize { self.n_frames }
+    pub fn agent_size(&self) -> f32 { self.agent_size }
+    pub fn set_agent_size(&mut self, size: f32) { self.agent_size = size }
+    pub fn trajectory(&self) -> &Trajectory<U, M::Cost> { &self.trajectory }
+    pub fn finish(self) -> Trajectory<U, M::Cost> { self.trajectory }
+
+    // appends the current state of `s` as a new frame
+    pub fn record(&mut self, s: &Simulator<M, U, T>) {
+        let t = self.n_frames;
+        let r = self.agent_size / 2.;
+
+        for (idx, a) in s.agents() {
+            let mut shape = vec![];
+            for seat in s.map().seats(a.current(), a.kind()) {
+                let (x, y) = (self.coordinate)(&seat);
+                shape.extend([(x - r, y - r), (x + r, y - r), (x + r, y + r), (x - r, y + r), (x - r, y - r)].map(Some));
+                shape.push(None);
+            }
+
+            let state = match a.state() {
+                AgentState::NotPlaced => "n",
+                AgentState::Stop => "s",
+                AgentState::Moving { nexts: _ } => "m",
+            };
+
+            let mut destinations = a.next_destinations()
+                .into_iter()
+                .flat_map(|d| d.ends().keys())
+                .flat_map(|n| s.map().seats(n, a.kind()))
+                .map(|seat| (self.coordinate)(&seat))
+                .collect::<Vec<_>>();
+            destinations.sort_by(|p, q| p.0.total_cmp(&q.0).then(p.1.total_cmp(&q.1)));
+            destinations.dedup();
+
+            self.trajectory.agents.entry(idx.value()).or_default().insert(t, TrajectoryAgent { shape, state: state.to_string(), destinations });
+        }
+
+        // the first agent reserving a seat is its occupant
+        let mut occupants = HashMap::new();
+        for &idx in s.agents().keys() {
+            for (seat, _) in s.reserved_seats(idx).unwrap_or_default() {
+                occupants.entry(seat).or_insert(idx.value());
+            }
+        }
+        for (i, seat) in self.seats.iter().enumerate() {
+            self.trajectory.seats[i].agent.push(occupants.get(seat).copied());
+        }
+
+        self.n_frames += 1;
+    }
+}
+
+impl<M: Map<U, T>, U: AgentIdxType + Ord + Serialize, F: FnMut(&M::SeatIndex) -> (f32, f32), T> TrajectoryRecorder<M, U, F, T> where M::Cost: Serialize {
+    pub fn write<W: Write>(&self, writer: W) -> serde_json::Result<()> {
+        serde_json::to_writer(writer, &self.trajectory)
+    }
+}
@@ -0,0 +1,89 @@
+#![cfg(feature = "cli")]
+
+use std::{path::Path, process::{Command, Output}};
+
+fn dmnav(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_dmnav"))
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cli/data"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn metric<'a>(output: &'a Output, key: &str) -> &'a str {
+    let stdout = std::str::from_utf8(&output.stdout).unwrap();
+    stdout.lines()
+        .find_map(|l| l.strip_prefix(key).and_then(|l| l.strip_prefix(": ")))
+        .unwrap()
+}
+
+#[test]
+fn ascii_grid_test() {
+    let path = std::env::temp_dir().join("dmnav_ascii_grid_test.csv");
+    let output = dmnav(&["grid.txt", "--max-reservation-time", "3", "--metrics", path.to_str().unwrap()]);
+
+    assert!(output.status.success());
+    assert_eq!(metric(&output, "result"), "completed");
+    assert_eq!(metric(&output, "completed agents"), "2");
+    assert_eq!(metric(&output, "makespan"), "6");
+
+    // header and one row per agent
+    let csv = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(csv.lines().count(), 3);
+    assert!(csv.starts_with("agent,"));
+}
+
+#[test]
+fn json_graph_test() {
+    for planner in ["astar", "dijkstra"] {
+        let output = dmnav(&["graph.json", "--planner", planner]);
+
+        assert!(output.status.success());
+        assert_eq!(metric(&output, "result"), "completed");
+        assert_eq!(metric(&output, "agents"), "2");
+    }
+}
+
+#[test]
+fn moving_ai_test() {
+    let path = std::env::temp_dir().join("dmnav_moving_ai_test.json");
+    let output = dmnav(&["small.map", "--scenario", "small.scen", "--agents", "2", "--trajectory", path.to_str().unwrap()]);
+
+    assert!(output.status.success());
+    assert_eq!(metric(&output, "agents"), "2");
+    assert_eq!(metric(&output, "result"), "completed");
+
+    let trajectory: serde_json::Value = serde_json::from_reader(std::fs::File::open(&path).unwrap()).unwrap();
+    // 20 cells without 4 blocked ones
+    assert_eq!(trajectory["seats"].as_array().unwrap().len(), 16);
+    assert_eq!(trajectory["agents"].as_object().unwrap().len(), 2);
+}
+
+#[test]
+fn scenario_test() {
+    let output = dmnav(&["experiment.toml"]);
+
+    assert!(output.status.success());
+    assert_eq!(metric(&output, "result"), "completed");
+    // "right" is removed at t = 20
+    assert_eq!(metric(&output, "agents"), "1");
+    assert!(metric(&output, "time").parse::<u32>().unwrap() > 20);
+}
+
+#[test]
+fn error_test() {
+    for args in [&["small.map"][..], &["grid.txt", "--planner", "foo"], &["missing.txt"], &[], &["experiment.toml", "--agents", "1"], &["experiment.toml", "--planner", "dijkstra"]] {
+        let output = dmnav(args);
+
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr).unwrap().starts_with("error: "));
+    }
+
+    let path = std::env::temp_dir().join("dmnav_error_test.toml");
+    let text = std::fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cli/data/experiment.toml")).unwrap();
+    let grid = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cli/data/grid.txt");
+    std::fs::write(&path, text.replace("start = [4, 0]", "start = [1, 1]").replace("grid.txt", grid.to_str().unwrap())).unwrap();
+
+    let output = dmnav(&[path.to_str().unwrap()]);
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("error: events[0].add.start: "));
+}
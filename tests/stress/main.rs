@@ -0,0 +1,227 @@
+use std::{collections::{HashMap, HashSet, VecDeque}, fmt::Debug, hash::Hash};
+
+use discrete_multi_nav::{agent_data::{AgentState, RemovalMode}, graph::GraphMap, grid::map::GridMap, index::index::Idx, map::Map, pathfind::common::MultipleEnds, seat::Seat, simulator::{Simulator, StopReason}, Error};
+use proptest::{collection::vec, prelude::*, test_runner::TestCaseError};
+
+// indices into the free nodes and the living agents are taken modulo their numbers so that any value is valid
+#[derive(Debug, Clone)]
+enum Op {
+    Add(usize, Vec<usize>),
+    Remove(usize, RemovalMode),
+    SetDestinations(usize, Vec<usize>),
+    CancelPath(usize),
+    Replan(usize),
+}
+
+#[derive(Debug, Clone)]
+struct Plan {
+    agents: Vec<(usize, Vec<usize>)>,
+    // (tick, operation)
+    ops: Vec<(usize, Op)>,
+    n_ticks: usize,
+}
+
+fn destinations() -> impl Strategy<Value = Vec<usize>> {
+    vec(any::<usize>(), 0..3)
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        (any::<usize>(), destinations()).prop_map(|(n, d)| Op::Add(n, d)),
+        (any::<usize>(), prop_oneof![Just(RemovalMode::Graceful), Just(RemovalMode::AtNextNode), Just(RemovalMode::Immediate)])
+            .prop_map(|(k, m)| Op::Remove(k, m)),
+        (any::<usize>(), destinations()).prop_map(|(k, d)| Op::SetDestinations(k, d)),
+        any::<usize>().prop_map(Op::CancelPath),
+        any::<usize>().prop_map(Op::Replan),
+    ]
+}
+
+fn plan() -> impl Strategy<Value = Plan> {
+    (vec((any::<usize>(), destinations()), 1..8), vec((0..40usize, op()), 0..8), 10..60usize)
+        .prop_map(|(agents, ops, n_ticks)| Plan { agents, ops, n_ticks })
+}
+
+// maps are generated as descriptions so that failures print and shrink to readable cases
+#[derive(Debug, Clone)]
+struct GridSpec {
+    nx: usize,
+    ny: usize,
+    blocked: Vec<bool>,
+}
+
+impl GridSpec {
+    fn build(&self) -> GridMap {
+        let mut map = GridMap::new(self.nx, self.ny);
+        for (k, &b) in self.blocked.iter().enumerate() {
+            map.set_blocked((k % self.nx, k / self.nx), b);
+        }
+        map
+    }
+}
+
+#[derive(Debug, Clone)]
+struct GraphSpec {
+    n: usize,
+    // (from, to, cost)
+    edges: Vec<(usize, usize, u32)>,
+}
+
+impl GraphSpec {
+    fn build(&self) -> GraphMap {
+        let mut map = GraphMap::new();
+        for k in 0..self.n {
+            map.add_node(k as f32, 0.);
+        }
+        for &(i, j, c) in self.edges.iter().filter(|(i, j, _)| i != j) {
+            map.add_edge(i, j, c);
+        }
+        map
+    }
+}
+
+fn grid() -> impl Strategy<Value = GridSpec> {
+    (2..7usize, 2..7usize)
+        .prop_flat_map(|(nx, ny)| vec(prop::bool::weighted(0.2), nx * ny).prop_map(move |blocked| GridSpec { nx, ny, blocked }))
+}
+
+fn graph() -> impl Strategy<Value = GraphSpec> {
+    (2..10usize)
+        .prop_flat_map(|n| vec((0..n, 0..n, 1..4u32), 0..3 * n).prop_map(move |edges| GraphSpec { n, edges }))
+}
+
+fn ends<N: Clone + Eq + Hash>(nodes: &[N], d: &[usize]) -> VecDeque<MultipleEnds<N, u32>> {
+    d.iter().map(|&k| MultipleEnds::new_as_all_zero(vec![nodes[k % nodes.len()].clone()])).collect()
+}
+
+// whether the agent can visit all of its destinations in order on the empty map
+fn reachable<M: Map<u32>>(map: &M, start: &M::Node, destinations: &VecDeque<MultipleEnds<M::Node, M::Cost>>) -> bool {
+    let mut current = vec![start.clone()];
+    for d in destinations {
+        let mut visited = current.iter().cloned().collect::<HashSet<_>>();
+        let mut queue = current.into_iter().collect::<VecDeque<_>>();
+        while let Some(n) = queue.pop_front() {
+            for (_, m, _) in map.successors(&n, &()) {
+                if visited.insert(m.clone()) {
+                    queue.push_back(m);
+                }
+            }
+        }
+        current = d.ends().keys().filter(|n| visited.contains(n)).cloned().collect();
+        if current.is_empty() {
+            return false
+        }
+    }
+    true
+}
+
+// placed agents never share a seat, neither under them nor in their reservations, and hold what they reserve
+fn check_collision<M: Map<u32>>(s: &Simulator<M, u32>) -> Result<(), TestCaseError> where M::SeatIndex: Hash + Debug {
+    let mut reserved = HashMap::new();
+    for (&idx, a) in s.agents() {
+        let seats = s.reserved_seats(idx).unwrap_or_default();
+        if *a.state() == AgentState::NotPlaced {
+            prop_assert!(seats.is_empty(), "agent {} reserves seats before it is placed", idx.value());
+            continue
+        }
+        for (seat, _) in seats {
+            if let Some(other) = reserved.insert(seat.clone(), idx) {
+                prop_assert!(false, "agents {} and {} reserve the seat {:?}", other.value(), idx.value(), seat);
+            }
+            prop_assert!(s.map()[seat.clone()].is_held_by(idx), "agent {} does not hold its reserved seat {:?}", idx.value(), seat);
+        }
+        for seat in s.map().seats(a.current(), a.kind()) {
+            prop_assert!(reserved.get(&seat) == Some(&idx), "agent {} does not reserve the seat {:?} under it", idx.value(), seat);
+        }
+    }
+    Ok(())
+}
+
+// agents are rejected exactly when their destinations cannot be reached
+fn add<M: Map<u32, Cost = u32>>(s: &mut Simulator<M, u32>, nodes: &[M::Node], n: usize, d: &[usize], alive: &mut Vec<Idx<(), u32>>) -> Result<(), TestCaseError> where M::Node: Debug, M::SeatIndex: Hash + Debug {
+    let (start, destinations) = (nodes[n % nodes.len()].clone(), ends(nodes, d));
+    let reachable = reachable(s.map(), &start, &destinations);
+    match s.add((), start, destinations) {
+        Ok(idx) => {
+            prop_assert!(reachable);
+            alive.push(idx);
+        },
+        Err(e) => prop_assert!(!reachable && matches!(e, Error::UnreachableDestination { .. }), "{}", e),
+    }
+    Ok(())
+}
+
+fn run<M: Map<u32, Cost = u32>, F: Fn(&M) -> bool>(map: M, nodes: Vec<M::Node>, plan: &Plan, is_free: F) -> Result<(), TestCaseError> where M::Node: Debug, M::SeatIndex: Hash + Debug {
+    if nodes.is_empty() {
+        return Ok(())
+    }
+    let mut s = Simulator::new(0, map, 3);
+    let mut alive = vec![];
+    for (n, d) in &plan.agents {
+        add(&mut s, &nodes, *n, d, &mut alive)?;
+    }
+
+    for tick in 0..plan.n_ticks {
+        for (_, op) in plan.ops.iter().filter(|(t, _)| *t == tick) {
+            match op {
+                Op::Add(n, d) => add(&mut s, &nodes, *n, d, &mut alive)?,
+                Op::Remove(k, mode) if !alive.is_empty() => {
+                    let idx = alive.remove(k % alive.len());
+                    prop_assert!(s.remove_with(idx, *mode).is_ok());
+                },
+                Op::SetDestinations(k, d) if !alive.is_empty() => {
+                    *s.agent_destination_mut(alive[k % alive.len()]).unwrap() = ends(&nodes, d);
+                },
+                Op::CancelPath(k) if !alive.is_empty() => {
+                    s.cancel_path(alive[k % alive.len()]).map_err(|e| TestCaseError::fail(e.to_string()))?;
+                },
+                Op::Replan(k) if !alive.is_empty() => {
+                    s.replan(alive[k % alive.len()]).map_err(|e| TestCaseError::fail(e.to_string()))?;
+                },
+                _ => {},
+            }
+        }
+        s.step().map_err(|e| TestCaseError::fail(format!("{} in the step at tick {}", e, tick)))?;
+        if let Err(e) = s.validate() {
+            prop_assert!(false, "{} after the step at tick {}", e, tick);
+        }
+        check_collision(&s)?;
+    }
+
+    // a lone agent is placed and reaches its destinations whenever they are reachable
+    let Some((&last, others)) = alive.split_last() else { return Ok(()) };
+    for &idx in others {
+        prop_assert!(s.remove(idx).is_ok());
+    }
+    let n_steps = 1000;
+    let reason = s.run_until_idle(n_steps).map_err(|e| TestCaseError::fail(e.to_string()))?;
+    let a = s.agent(last).unwrap();
+    if reachable(s.map(), a.current(), a.all_destinations()) {
+        prop_assert_eq!(reason, StopReason::Completed);
+        prop_assert_eq!(s.agents().len(), 1);
+    }
+
+    // every reservation is released once the agents are gone
+    prop_assert!(s.remove(last).is_ok());
+    s.run_until_idle(n_steps).map_err(|e| TestCaseError::fail(e.to_string()))?;
+    prop_assert!(s.agents().is_empty());
+    prop_assert!(is_free(s.map()));
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig { failure_persistence: None, ..ProptestConfig::with_cases(128) })]
+
+    #[test]
+    fn grid_test(map in grid(), plan in plan()) {
+        let map = map.build();
+        let nodes = (0..map.nx()).flat_map(|x| (0..map.ny()).map(move |y| (x, y))).filter(|&p| !map.is_blocked(p)).collect::<Vec<_>>();
+        run(map, nodes, &plan, |m| (0..m.nx()).all(|x| (0..m.ny()).all(|y| m.occupant((x, y)).is_none())))?;
+    }
+
+    #[test]
+    fn graph_test(map in graph(), plan in plan()) {
+        let map = map.build();
+        let nodes = (0..map.len()).collect::<Vec<_>>();
+        run(map, nodes, &plan, |m| m.nodes().iter().all(|n| n.occupant().is_none()))?;
+    }
+}
@@ -1,48 +1,75 @@
-use std::{collections::HashMap, fs::File, path::Path};
+#![allow(clippy::type_complexity)]
+// the map accessors for the viewer output are unused without the trajectory feature
+#![cfg_attr(not(feature = "trajectory"), allow(dead_code))]
 
-use discrete_multi_nav::{agent_data::AgentState, index::index::Idx, simulator::Simulator};
-use json_schema::{Agent, Data};
+#[cfg(feature = "trajectory")]
+use std::{collections::BTreeMap, fs::File, path::Path};
+
+use discrete_multi_nav::{index::index::Idx, simulator::Simulator};
+#[cfg(feature = "trajectory")]
+use discrete_multi_nav::{agent_data::AgentState, trajectory::{Trajectory, TrajectoryAgent, TrajectorySeat}};
 use test_map::TestMap;
 
 extern crate discrete_multi_nav;
 
 mod test_map;
 mod test_node;
-mod json_schema;
 
 mod test1;
 mod test2;
 
-fn output_file(filename: &String, output: &Data) {
-    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
-        .join("tests/visual/viewer/outputs");
-    let f = File::create(path.join(filename)).unwrap();
-    serde_json::to_writer(f, &output).unwrap();
+// frames for the viewer, which reads the schema of the trajectory feature, so nothing is written without it
+struct Output {
+    #[cfg(feature = "trajectory")]
+    trajectory: Trajectory<u32, u32>,
 }
 
-fn output_data<const N: usize>(s: &Simulator<TestMap, u32>, map: &[(u32, u32, Vec<(usize, u32)>); N], t: i32, output: &mut Data, idxs: &Vec<Idx<(), u32>>) {
+#[cfg(feature = "trajectory")]
+impl Output {
+    fn new(s: &Simulator<TestMap, u32>) -> Self {
+        let seats = s.map().nodes().iter()
+            .map(|n| TrajectorySeat { x: n.x() as f32, y: n.y() as f32, nexts: n.nexts().clone(), agent: vec![] })
+            .collect();
+        Self { trajectory: Trajectory { seats, agents: BTreeMap::new() } }
+    }
+
+    fn record<const N: usize>(&mut self, s: &Simulator<TestMap, u32>, map: &[(u32, u32, Vec<(usize, u32)>); N], t: usize, idxs: &Vec<Idx<(), u32>>) {
+        for i0 in idxs {
+            let Some(a0) = s.agent(*i0) else { continue };
+
+            let mut shape = vec![];
+            for &i in a0.current() {
+                let &(x, y, _) = &map[i];
+                let (x, y) = (x as f32, y as f32);
+                shape.extend([(x - 0.4, y - 0.4), (x + 0.4, y - 0.4), (x + 0.4, y + 0.4), (x - 0.4, y + 0.4), (x - 0.4, y - 0.4)].iter().map(|&p| Some(p)));
+                shape.push(None);
+            }
 
-    for i0 in idxs {
-        let Some(a0) = s.agent(*i0) else { continue };
+            let state = match a0.state() {
+                AgentState::NotPlaced => "n",
+                AgentState::Stop => "s",
+                AgentState::Moving { nexts: _ } => "m",
+            };
 
-        let mut shape = vec![];
-        for &i in a0.current() {
-            let &(x, y, _) = &map[i];
-            let (x, y) = (x as f32, y as f32);
-            shape.extend([(x - 0.4, y - 0.4), (x + 0.4, y - 0.4), (x + 0.4, y + 0.4), (x - 0.4, y + 0.4), (x - 0.4, y - 0.4)].iter().map(|&p| Some(p)));
-            shape.push(None);
+            self.trajectory.agents.entry(i0.value()).or_default().insert(t, TrajectoryAgent { shape, state: state.to_string(), destinations: vec![] });
+        }
+
+        for (i, n) in s.map().nodes().iter().enumerate() {
+            self.trajectory.seats[i].agent.push(n.occupied().map(|n| n.value()));
         }
-        
-        let state = match a0.state() {
-            AgentState::NotPlaced => "n",
-            AgentState::Stop => "s",
-            AgentState::Moving { nexts: _ } => "m",
-        };
-
-        output.agents.entry(i0.value()).or_insert(HashMap::new()).insert(t, Agent { shape, state: state.to_string() });
     }
 
-    for (i, n) in s.map().nodes().iter().enumerate() {
-        output.seats[i].agent.push(n.occupied().and_then(|n| Some(n.value())));
+    fn write(&self, filename: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/visual/viewer/outputs");
+        let f = File::create(path.join(filename)).unwrap();
+        serde_json::to_writer(f, &self.trajectory).unwrap();
     }
 }
+
+#[cfg(not(feature = "trajectory"))]
+impl Output {
+    fn new(_s: &Simulator<TestMap, u32>) -> Self { Self {} }
+    fn record<const N: usize>(&mut self, _s: &Simulator<TestMap, u32>, _map: &[(u32, u32, Vec<(usize, u32)>); N], _t: usize, _idxs: &Vec<Idx<(), u32>>) {}
+    fn write(&self, _filename: &str) {}
+}
@@ -1,9 +1,9 @@
 use std::collections::{HashMap, VecDeque};
 
 use discrete_multi_nav::{pathfind::common::MultipleEnds, simulator::Simulator};
-use crate::{json_schema::{Data, Seat}, output_data, output_file, test_map::TestMap, test_node::TestNode};
+use crate::{test_map::TestMap, test_node::TestNode, Output};
 
-fn testdata1(max_reservation_time: u32) -> ([(u32, u32, Vec<(usize, u32)>); 14], Simulator<TestMap, u32>, Vec<Seat>) {
+fn testdata1(max_reservation_time: u32) -> ([(u32, u32, Vec<(usize, u32)>); 14], Simulator<TestMap, u32>) {
     //  10 <- 9 <- 8 <--  7 <- 6
     //   v         v           ^
     //  11        12 <-> 13 -> 5 
@@ -27,171 +27,213 @@ fn testdata1(max_reservation_time: u32) -> ([(u32, u32, Vec<(usize, u32)>); 14],
         (3, 1, vec![(5, 1), (12, 1)]),
     ];
     
-    let ns = map.iter().map(|(x, y, js)| TestNode::new(*x, *y, js.iter().map(|&j| j).collect::<Vec<_>>())).collect::<Vec<_>>();
-    let seats = ns.iter()
-        .map(|n| Seat{ x: n.x() as i32, y: n.y() as i32, nexts: n.nexts().clone(), agent: vec![] })
-        .collect::<Vec<_>>();
+    let ns = map.iter().map(|(x, y, js)| TestNode::new(*x, *y, js.to_vec())).collect::<Vec<_>>();
 
     let m = TestMap::new(ns);
     let s = Simulator::new(0, m, max_reservation_time);
 
-    (map, s, seats)
+    (map, s)
 }
 
 
 #[test]
 fn test1_1() {
 
-    let (map, mut s, seats) = testdata1(3);
+    let (map, mut s) = testdata1(3);
 
-    let i0 = s.add((), VecDeque::from([1, 0]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([8, 7])])]));
+    let i0 = s.add((), VecDeque::from([1, 0]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([8, 7])])])).unwrap();
     
-    let mut output = Data{ seats, agents: HashMap::new() };
+    let mut output = Output::new(&s);
 
-    output_data(&s, &map, 0, &mut output, &vec![i0]);
+    output.record(&s, &map, 0, &vec![i0]);
     for t in 1..=12 {
-        s.step();
-        output_data(&s, &map, t, &mut output, &vec![i0])
+        s.step().unwrap();
+        output.record(&s, &map, t, &vec![i0])
     }
 
-    output_file(&"test1-1.json".to_string(), &output);
+    output.write("test1-1.json");
 }
 
 #[test]
 fn test1_2() {
 
-    let (map, mut s, seats) = testdata1(3);
+    let (map, mut s) = testdata1(3);
 
-    let i0 = s.add((), VecDeque::from([0, 11, 10]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([7, 6, 5])])]));
-    let i1 = s.add((), VecDeque::from([7, 6]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([13, 3])])]));
+    let i0 = s.add((), VecDeque::from([0, 11, 10]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([7, 6, 5])])])).unwrap();
+    let i1 = s.add((), VecDeque::from([7, 6]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([13, 3])])])).unwrap();
     
-    let mut output = Data{ seats, agents: HashMap::new() };
+    let mut output = Output::new(&s);
 
-    output_data(&s, &map, 0, &mut output, &vec![i0, i1]);
+    output.record(&s, &map, 0, &vec![i0, i1]);
     for t in 1..=12 {
-        s.step();
-        output_data(&s, &map, t, &mut output, &vec![i0, i1])
+        s.step().unwrap();
+        output.record(&s, &map, t, &vec![i0, i1])
     }
 
-    output_file(&"test1-2.json".to_string(), &output);
+    output.write("test1-2.json");
 }
 
 #[test]
 fn test1_3() {
 
-    let (map, mut s, seats) = testdata1(3);
+    let (map, mut s) = testdata1(3);
 
     let idxs = (0..3)
-        .map(|_| s.add((), VecDeque::from([7, 6]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([5, 4])])])))
+        .map(|_| s.add((), VecDeque::from([7, 6]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([5, 4])])])).unwrap())
         .collect::<Vec<_>>();
     
-    let mut output = Data{ seats, agents: HashMap::new()};
+    let mut output = Output::new(&s);
 
-    output_data(&s, &map, 0, &mut output, &idxs);
+    output.record(&s, &map, 0, &idxs);
     for t in 1..=12 {
-        s.step();
-        output_data(&s, &map, t, &mut output, &idxs)
+        s.step().unwrap();
+        output.record(&s, &map, t, &idxs)
     }
 
-    output_file(&"test1-3.json".to_string(), &output);
+    output.write("test1-3.json");
 }
 
 #[test]
 fn test1_4() {
 
-    let (map, mut s, seats) = testdata1(3);
+    let (map, mut s) = testdata1(3);
 
     let idxs = [0, 1, 7, 8, 10, 11]
         .iter()
-        .map(|&i| s.add((), VecDeque::from([i]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([10])])])))
+        .map(|&i| s.add((), VecDeque::from([i]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([10])])])).unwrap())
         .collect::<Vec<_>>();
     
-    let mut output = Data{ seats, agents: HashMap::new()};
+    let mut output = Output::new(&s);
 
-    output_data(&s, &map, 0, &mut output, &idxs);
+    output.record(&s, &map, 0, &idxs);
     for t in 1..=12 {
-        s.step();
-        output_data(&s, &map, t, &mut output, &idxs)
+        s.step().unwrap();
+        output.record(&s, &map, t, &idxs)
     }
 
-    output_file(&"test1-4.json".to_string(), &output);
+    output.write("test1-4.json");
 }
 
 #[test]
 fn test1_5() {
 
-    let (map, mut s, seats) = testdata1(3);
+    let (map, mut s) = testdata1(3);
 
     let i0 = s.add((), VecDeque::from([1, 0, 11]), VecDeque::from([
         MultipleEnds::new_as_all_zero(vec![VecDeque::from([2, 12, 13])]),
         MultipleEnds::new_as_all_zero(vec![VecDeque::from([1, 0, 11])]),
-    ]));
+    ])).unwrap();
 
     let idxs = vec![i0];
     
-    let mut output = Data{ seats, agents: HashMap::new()};
+    let mut output = Output::new(&s);
 
-    output_data(&s, &map, 0, &mut output, &idxs);
+    output.record(&s, &map, 0, &idxs);
     for t in 1..=20 {
-        s.step();
-        output_data(&s, &map, t, &mut output, &idxs)
+        s.step().unwrap();
+        output.record(&s, &map, t, &idxs)
     }
 
-    output_file(&"test1-5.json".to_string(), &output);
+    output.write("test1-5.json");
 }
 
 #[test]
 fn test1_6() {
 
-    let (map, mut s, seats) = testdata1(3);
+    let (map, mut s) = testdata1(3);
 
     let i0 = s.add((), VecDeque::from([1, 0, 11]), VecDeque::from([
         MultipleEnds::new_as_all_zero(vec![VecDeque::from([2, 12, 13])]),
         MultipleEnds::new_as_all_zero(vec![VecDeque::from([1, 0, 11])]),
-    ]));
+    ])).unwrap();
 
     let i1 = s.add((), VecDeque::from([6, 5]), VecDeque::from([
         MultipleEnds::new_as_all_zero(vec![VecDeque::from([13, 12])]),
         MultipleEnds::new_as_all_zero(vec![VecDeque::from([11, 10])]),
-    ]));
+    ])).unwrap();
 
     let idxs = vec![i0, i1];
     
-    let mut output = Data{ seats, agents: HashMap::new()};
+    let mut output = Output::new(&s);
 
-    output_data(&s, &map, 0, &mut output, &idxs);
+    output.record(&s, &map, 0, &idxs);
     for t in 1..=8 {
-        s.step();
-        output_data(&s, &map, t, &mut output, &idxs)
+        s.step().unwrap();
+        output.record(&s, &map, t, &idxs)
     }
 
-    output_file(&"test1-6.json".to_string(), &output);
+    output.write("test1-6.json");
 }
 
 #[test]
 fn test1_7() {
 
-    let (map, mut s, seats) = testdata1(3);
+    let (map, mut s) = testdata1(3);
 
     let i0 = s.add((), VecDeque::from([4, 3]), VecDeque::from([
         MultipleEnds::new(HashMap::from([(VecDeque::from([2, 12]), 2), (VecDeque::from([10, 9]), 0)])),
         MultipleEnds::new_as_all_zero(vec![VecDeque::from([4, 3])]),
-    ]));
+    ])).unwrap();
 
     let i1 = s.add((), VecDeque::from([7, 6]), VecDeque::from([
         MultipleEnds::new(HashMap::from([(VecDeque::from([2, 12]), 3), (VecDeque::from([2, 1]), 0)])),
         MultipleEnds::new_as_all_zero(vec![VecDeque::from([7, 6])]),
-    ]));
+    ])).unwrap();
 
     let idxs = vec![i0, i1];
     
-    let mut output = Data{ seats, agents: HashMap::new()};
+    let mut output = Output::new(&s);
 
-    output_data(&s, &map, 0, &mut output, &idxs);
+    output.record(&s, &map, 0, &idxs);
     for t in 1..=15 {
-        s.step();
-        output_data(&s, &map, t, &mut output, &idxs)
+        s.step().unwrap();
+        output.record(&s, &map, t, &idxs)
+    }
+
+    output.write("test1-7.json");
+}
+
+#[cfg(feature = "trajectory")]
+#[test]
+fn trajectory_test() {
+    use discrete_multi_nav::trajectory::{Trajectory, TrajectoryRecorder};
+
+    let (map, mut s) = testdata1(3);
+
+    let i0 = s.add((), VecDeque::from([0, 11, 10]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([7, 6, 5])])])).unwrap();
+    let i1 = s.add((), VecDeque::from([7, 6]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([13, 3])])])).unwrap();
+
+    let mut output = Output::new(&s);
+    let mut recorder = TrajectoryRecorder::new_with_nexts(
+        map.iter().enumerate().map(|(i, (_, _, nexts))| (i, nexts.clone())).collect(),
+        |&i: &usize| (map[i].0 as f32, map[i].1 as f32),
+    );
+
+    output.record(&s, &map, 0, &vec![i0, i1]);
+    recorder.record(&s);
+    for t in 1..=12 {
+        s.step().unwrap();
+        output.record(&s, &map, t, &vec![i0, i1]);
+        recorder.record(&s);
+    }
+
+    let mut buf = vec![];
+    recorder.write(&mut buf).unwrap();
+    let trajectory: Trajectory<u32, u32> = serde_json::from_slice(&buf).unwrap();
+
+    assert_eq!(recorder.n_frames(), 13);
+    assert_eq!(trajectory.seats, output.trajectory.seats);
+    for (i, frames) in &output.trajectory.agents {
+        for (t, a) in frames {
+            let b = &trajectory.agents[i][t];
+            assert_eq!((&a.shape, &a.state), (&b.shape, &b.state));
+        }
     }
 
-    output_file(&"test1-7.json".to_string(), &output);
+    let mut recorder = TrajectoryRecorder::new(vec![], |&i: &usize| (map[i].0 as f32, map[i].1 as f32));
+    recorder.set_agent_size(1.);
+    recorder.record(&s);
+    let seat = s.agent(i0).unwrap().current()[0];
+    let (x, y) = (map[seat].0 as f32, map[seat].1 as f32);
+    assert_eq!(recorder.trajectory().agents[&i0.value()][&0].shape[..2], [Some((x - 0.5, y - 0.5)), Some((x + 0.5, y - 0.5))]);
 }
@@ -1,9 +1,9 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::VecDeque;
 
 use discrete_multi_nav::{pathfind::common::MultipleEnds, simulator::Simulator};
-use crate::{json_schema::{Data, Seat}, output_data, output_file, test_map::TestMap, test_node::TestNode};
+use crate::{test_map::TestMap, test_node::TestNode, Output};
 
-fn testdata2(max_reservation_time: u32) -> ([(u32, u32, Vec<(usize, u32)>); 20], Simulator<TestMap, u32>, Vec<Seat>) {
+fn testdata2(max_reservation_time: u32) -> ([(u32, u32, Vec<(usize, u32)>); 20], Simulator<TestMap, u32>) {
     
     //      6 -> 7 -> * -> 8 -> * -> 17 -> * -> 18 -> * -> 19
     //                          |                         ^
@@ -40,80 +40,77 @@ fn testdata2(max_reservation_time: u32) -> ([(u32, u32, Vec<(usize, u32)>); 20],
         (10, 3, vec![]),
     ];
     
-    let ns = map.iter().map(|(x, y, js)| TestNode::new(*x, *y, js.iter().map(|&j| j).collect::<Vec<_>>())).collect::<Vec<_>>();
-    let seats = ns.iter()
-        .map(|n| Seat{ x: n.x() as i32, y: n.y() as i32, nexts: n.nexts().clone(), agent: vec![] })
-        .collect::<Vec<_>>();
+    let ns = map.iter().map(|(x, y, js)| TestNode::new(*x, *y, js.to_vec())).collect::<Vec<_>>();
 
     let m = TestMap::new(ns);
     let s = Simulator::new(0, m, max_reservation_time);
 
-    (map, s, seats)
+    (map, s)
 }
 
 #[test]
 fn test2_1() {
 
-    let (map, mut s, seats) = testdata2(3);
+    let (map, mut s) = testdata2(3);
 
     let i0 = s.add((), VecDeque::from([0]), VecDeque::from([
         MultipleEnds::new_as_all_zero(vec![VecDeque::from([10])]),
         MultipleEnds::new_as_all_zero(vec![VecDeque::from([13])])
-    ]));
+    ])).unwrap();
     
-    let mut output = Data{ seats, agents: HashMap::new() };
+    let mut output = Output::new(&s);
 
-    output_data(&s, &map, 0, &mut output, &vec![i0]);
+    output.record(&s, &map, 0, &vec![i0]);
     for t in 1..=15 {
-        s.step();
-        output_data(&s, &map, t, &mut output, &vec![i0])
+        s.step().unwrap();
+        output.record(&s, &map, t, &vec![i0])
     }
 
-    output_file(&"test2-1.json".to_string(), &output);
+    output.write("test2-1.json");
 }
 
 #[test]
 fn test2_2() {
 
-    let (map, mut s, seats) = testdata2(3);
+    let (map, mut s) = testdata2(3);
 
-    let i0 = s.add((), VecDeque::from([3]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([16])])]));
-    let i1 = s.add((), VecDeque::from([6]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([13])])]));
-    let i2 = s.add((), VecDeque::from([0]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([19])])]));
-    let i3 = s.add((), VecDeque::from([3]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([19])])]));
-    let i4 = s.add((), VecDeque::from([0]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([16])])]));
-    let i5 = s.add((), VecDeque::from([6]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([13])])]));
-    let i6 = s.add((), VecDeque::from([6]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([13])])]));
+    let i0 = s.add((), VecDeque::from([3]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([16])])])).unwrap();
+    let i1 = s.add((), VecDeque::from([6]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([13])])])).unwrap();
+    let i2 = s.add((), VecDeque::from([0]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([19])])])).unwrap();
+    let i3 = s.add((), VecDeque::from([3]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([19])])])).unwrap();
+    let i4 = s.add((), VecDeque::from([0]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([16])])])).unwrap();
+    let i5 = s.add((), VecDeque::from([6]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([13])])])).unwrap();
+    let i6 = s.add((), VecDeque::from([6]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([13])])])).unwrap();
 
     let idxs = vec![i0, i1, i2, i3, i4, i5, i6];
     
-    let mut output = Data{ seats, agents: HashMap::new() };
+    let mut output = Output::new(&s);
 
-    output_data(&s, &map, 0, &mut output, &idxs);
+    output.record(&s, &map, 0, &idxs);
     for t in 1..=27 {
-        s.step();
-        output_data(&s, &map, t, &mut output, &idxs);
+        s.step().unwrap();
+        output.record(&s, &map, t, &idxs);
 
         if t == 1 {
-            s.remove(i5);
+            s.remove(i5).unwrap();
         } else if t == 10 {
-            s.remove(i1);
+            s.remove(i1).unwrap();
         } else if t == 20 {
-            s.remove(i3);
+            s.remove(i3).unwrap();
         }
     }
 
-    output_file(&"test2-2.json".to_string(), &output);
+    output.write("test2-2.json");
 }
 
 #[test]
 fn test2_3() {
 
-    let (map, mut s, seats) = testdata2(3);
+    let (map, mut s) = testdata2(3);
 
     let mut idxs = vec![];
 
-    let mut output = Data{ seats, agents: HashMap::new() };
+    let mut output = Output::new(&s);
 
     let mut routes = VecDeque::from([
         (0, 16),
@@ -132,22 +129,22 @@ fn test2_3() {
         (0, 16),
     ]);
 
-    output_data(&s, &map, 0, &mut output, &idxs);
+    output.record(&s, &map, 0, &idxs);
 
     let mut j = 0;
     for t in 1..=60 {
         if !routes.is_empty() && t % 2 == 1 {
             let (from, to) = routes.pop_front().unwrap();
-            let i = s.add((), VecDeque::from([from]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([to])])]));
+            let i = s.add((), VecDeque::from([from]), VecDeque::from([MultipleEnds::new_as_all_zero(vec![VecDeque::from([to])])])).unwrap();
             idxs.push(i);
         }
         if t > 20 && t % 3 == 0 {
-            s.remove(idxs[j]);
+            s.remove(idxs[j]).unwrap();
             j += 1;
         }
-        s.step();
-        output_data(&s, &map, t, &mut output, &idxs);
+        s.step().unwrap();
+        output.record(&s, &map, t, &idxs);
     }
 
-    output_file(&"test2-3.json".to_string(), &output);
+    output.write("test2-3.json");
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
 use std::{collections::{vec_deque, VecDeque}, ops::{Index, IndexMut}, vec::IntoIter};
 
-use discrete_multi_nav::{index::index::Idx, map::{DummyHeuristic, Map}, seat::Seat};
+use discrete_multi_nav::{index::index::Idx, map::{DummyHeuristic, Map}, seat::{Seat, SeatError}};
 
 use crate::test_node::TestNode;
 
@@ -91,11 +91,19 @@ impl Seat<(), u32> for TestNode {
         }
     }
 
-    fn add(&mut self, idx: Idx<(), u32>) {
-        self.set_occupied(Some(idx))
+    fn add(&mut self, idx: Idx<(), u32>) -> Result<(), SeatError> {
+        if !self.is_empty_for(idx) {
+            return Err(SeatError::AlreadyOccupied)
+        }
+        self.set_occupied(Some(idx));
+        Ok(())
     }
 
-    fn remove(&mut self, _: Idx<(), u32>) {
-        self.set_occupied(None)
+    fn remove(&mut self, idx: Idx<(), u32>) -> Result<(), SeatError> {
+        if self.occupied() != Some(idx) {
+            return Err(SeatError::NotHeld)
+        }
+        self.set_occupied(None);
+        Ok(())
     }
 }
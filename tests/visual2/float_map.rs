@@ -0,0 +1,115 @@
+use std::{ops::{Index, IndexMut}, vec::IntoIter};
+
+use discrete_multi_nav::{cost::FloatCost, index::index::Idx, map::{DummyHeuristic, Map}, seat::{Seat, SeatError}};
+
+// 8-connected grid whose diagonal moves cost sqrt(2) times `scale`
+pub(crate) struct FloatMap {
+    occupied: Vec<Vec<FloatNode>>,
+    scale: f64,
+}
+
+impl FloatMap {
+    pub(crate) fn new(nx: usize, ny: usize) -> Self {
+        Self::with_scale(nx, ny, 1.)
+    }
+
+    pub(crate) fn with_scale(nx: usize, ny: usize, scale: f64) -> Self {
+        Self { occupied: (0..nx).map(|_| vec![FloatNode(None); ny]).collect::<Vec<_>>(), scale }
+    }
+
+    pub(crate) fn nx(&self) -> usize { self.occupied.len() }
+    pub(crate) fn ny(&self) -> usize { self.occupied[0].len() }
+
+    fn cost(&self, i: usize) -> FloatCost {
+        if i % 2 == 0 { FloatCost::new(self.scale) } else { FloatCost::new(2f64.sqrt() * self.scale) }
+    }
+}
+
+const DXYS: [(i32, i32); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+
+impl Map<u32> for FloatMap {
+    type Cost = FloatCost;
+    type I = usize;
+    type SeatIndex = (usize, usize);
+    type Seat = FloatNode;
+    type Node = (usize, usize);
+
+    type SIter = IntoIter<Self::SeatIndex>;
+    type SCIter = IntoIter<(Self::I, Self::Node, Self::Cost)>;
+    type SBIter = IntoIter<(Self::SeatIndex, Self::Cost)>;
+
+    type FH = DummyHeuristic;
+
+    fn seats(&self, n: &Self::Node, _: &()) -> Self::SIter {
+        vec![*n].into_iter()
+    }
+
+    fn successors(&self, n: &Self::Node, t: &()) -> Self::SCIter {
+        (0..DXYS.len())
+            .filter_map(|i| self.successor(n, t, &i).map(|m| (i, m, self.cost(i))))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    fn successor(&self, &(x, y): &Self::Node, _: &(), &i: &Self::I) -> Option<Self::Node> {
+        let (dx, dy) = DXYS.get(i)?;
+        let (x, y) = ((x as i32 + dx), (y as i32 + dy));
+        if 0 <= x && x < self.nx() as i32 && 0 <= y && y < self.ny() as i32 {
+            Some((x as usize, y as usize))
+        } else {
+            None
+        }
+    }
+
+    // the departing cell is held until the arrival
+    fn seats_between(&self, n: &Self::Node, _: &(), &i: &Self::I) -> Self::SBIter {
+        vec![(*n, self.cost(i))].into_iter()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct FloatNode(Option<Idx<(), u32>>);
+
+impl FloatNode {
+    pub(crate) fn get(&self) -> Option<Idx<(), u32>> { self.0 }
+}
+
+impl Index<(usize, usize)> for FloatMap {
+    type Output = FloatNode;
+
+    fn index(&self, (i, j): (usize, usize)) -> &Self::Output {
+        &self.occupied[i][j]
+    }
+}
+
+impl IndexMut<(usize, usize)> for FloatMap {
+    fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut Self::Output {
+        &mut self.occupied[i][j]
+    }
+}
+
+impl Seat<(), u32> for FloatNode {
+    fn is_empty_for(&self, i: Idx<(), u32>) -> bool {
+        if let Some(j) = self.0 {
+            i == j
+        } else {
+            true
+        }
+    }
+
+    fn add(&mut self, idx: Idx<(), u32>) -> Result<(), SeatError> {
+        if !self.is_empty_for(idx) {
+            return Err(SeatError::AlreadyOccupied)
+        }
+        self.0 = Some(idx);
+        Ok(())
+    }
+
+    fn remove(&mut self, idx: Idx<(), u32>) -> Result<(), SeatError> {
+        if self.0 != Some(idx) {
+            return Err(SeatError::NotHeld)
+        }
+        self.0 = None;
+        Ok(())
+    }
+}
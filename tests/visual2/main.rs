@@ -1,13 +1,15 @@
 use std::{collections::{HashMap, HashSet, VecDeque}, fs::File, io::Write, path::Path, time::Instant};
 
-use discrete_multi_nav::{agent_data::AgentState, index::index::Idx, map::Movement, pathfind::common::MultipleEnds, simulator::Simulator};
+use discrete_multi_nav::{agent_data::AgentState, cost::FloatCost, index::index::Idx, map::Movement, pathfind::common::MultipleEnds, simulator::{Clock, Planner, Simulator, StopReason}, Error};
+use float_map::FloatMap;
 use map::TestMap;
-use rand::{thread_rng, Rng};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use serde::Serialize;
 
 extern crate discrete_multi_nav;
 
 mod map;
+mod float_map;
 
 #[derive(Serialize)]
 pub(crate) struct Data {
@@ -32,17 +34,17 @@ fn output_file(filename: &String, output: &Vec<Data>) {
 }
 
 
-fn output_data(s: &Simulator<TestMap, u32>, idxs: &Vec<Idx<(), u32>>, t: u32) -> Data {
+fn output_data(s: &Simulator<TestMap, u32>, idxs: &[Idx<(), u32>], t: u32) -> Data {
 
     let map = (0..s.map().nx())
-        .map(|x| (0..s.map().ny()).map(move |y| s.map()[(x, y)].get().and_then(|i| Some(i.value()))).collect::<Vec<_>>())
+        .map(|x| (0..s.map().ny()).map(move |y| s.map()[(x, y)].get().map(|i| i.value())).collect::<Vec<_>>())
         .collect::<Vec<_>>();
 
     let agents = idxs
-        .into_iter()
+        .iter()
         .filter_map(|&i| 
             s.agent(i)
-                .and_then(|a| {
+                .map(|a| {
                     let &(x, y) = a.current();
                     let state = match a.state() {
                         AgentState::NotPlaced => "n",
@@ -55,8 +57,8 @@ fn output_data(s: &Simulator<TestMap, u32>, idxs: &Vec<Idx<(), u32>>, t: u32) ->
                         None
                     };
                     let dest = a.next_destinations()
-                        .and_then(|m| Some(m.ends().keys().copied().collect::<Vec<_>>()) );
-                    Some((i.value(), Agent { x, y, state: state.to_string(), next, dest }))
+                        .map(|m| m.ends().keys().copied().collect::<Vec<_>>());
+                    (i.value(), Agent { x, y, state: state.to_string(), next, dest })
                 })
         )
         .collect::<HashMap<_, _>>();
@@ -71,17 +73,14 @@ fn test1() {
     let i0 = s.add((), (0, 0), VecDeque::from([
         MultipleEnds::new_as_all_zero(vec![(7, 4)]),
         MultipleEnds::new_as_all_zero(vec![(0, 0)]),
-    ]));
+    ])).unwrap();
     let idxs = vec![i0];
 
     let mut output = vec![];
 
     output.push(output_data(&s, &idxs, 0));
 
-    for t in 1..=42 {
-        s.step();
-        output.push(output_data(&s, &idxs, t));
-    }
+    s.run_for(42, |s| output.push(output_data(s, &idxs, s.time()))).unwrap();
 
     output_file(&"test1.json".to_string(), &output);
 }
@@ -93,21 +92,18 @@ fn test2() {
     let i0 = s.add((), (0, 0), VecDeque::from([
         MultipleEnds::new_as_all_zero(vec![(7, 4)]),
         MultipleEnds::new_as_all_zero(vec![(0, 0)]),
-    ]));
+    ])).unwrap();
     let i1 = s.add((), (0, 4), VecDeque::from([
         MultipleEnds::new_as_all_zero(vec![(7, 0)]),
         MultipleEnds::new_as_all_zero(vec![(0, 4)]),
-    ]));
+    ])).unwrap();
     let idxs = vec![i0, i1];
 
     let mut output = vec![];
 
     output.push(output_data(&s, &idxs, 0));
 
-    for t in 1..=53 {
-        s.step();
-        output.push(output_data(&s, &idxs, t));
-    }
+    s.run_for(53, |s| output.push(output_data(s, &idxs, s.time()))).unwrap();
 
     output_file(&"test2.json".to_string(), &output);
 }
@@ -122,8 +118,8 @@ fn test3() {
     for i in 0..3 {
         let j = s.add((), (0, 0), VecDeque::from_iter(
             (0..3).map(|k| MultipleEnds::new_as_all_zero(vec![ps[(i + k) % 3]]))
-                .chain(vec![MultipleEnds::new_as_all_zero(vec![(0, 0)])].into_iter())
-        ));
+                .chain(vec![MultipleEnds::new_as_all_zero(vec![(0, 0)])])
+        )).unwrap();
         idxs.push(j);
     }
 
@@ -131,20 +127,11 @@ fn test3() {
 
     output.push(output_data(&s, &idxs, 0));
 
-    for t in 1..=124 {
-        s.step();
-        output.push(output_data(&s, &idxs, t));
-    }
-    s.remove(idxs[0]);
-    for t in 125..=128 {
-        s.step();
-        output.push(output_data(&s, &idxs, t));
-    }
-    s.remove(idxs[1]);
-    for t in 128..=134 {
-        s.step();
-        output.push(output_data(&s, &idxs, t));
-    }
+    s.run_for(124, |s| output.push(output_data(s, &idxs, s.time()))).unwrap();
+    s.remove(idxs[0]).unwrap();
+    s.run_for(4, |s| output.push(output_data(s, &idxs, s.time()))).unwrap();
+    s.remove(idxs[1]).unwrap();
+    s.run_for(7, |s| output.push(output_data(s, &idxs, s.time()))).unwrap();
 
     output_file(&"test3.json".to_string(), &output);
 }
@@ -162,7 +149,7 @@ fn test4() {
         MultipleEnds::new_as_all_zero(vec![(0, 7), (7, 0)]),
         MultipleEnds::new_as_all_zero(vec![(7, 7)]),
         MultipleEnds::new_as_all_zero(vec![(0, 0)]),
-    ]));
+    ])).unwrap();
     let i1 = s.add((), (7, 7), VecDeque::from([
         MultipleEnds::new_as_all_zero(vec![(3, 4), (4, 3)]),
         MultipleEnds::new_as_all_zero(vec![(0, 7), (7, 0)]),
@@ -172,7 +159,7 @@ fn test4() {
         MultipleEnds::new_as_all_zero(vec![(0, 7), (7, 0)]),
         MultipleEnds::new_as_all_zero(vec![(0, 0)]),
         MultipleEnds::new_as_all_zero(vec![(7, 7)]),
-    ]));
+    ])).unwrap();
     let i2 = s.add((), (7, 0), VecDeque::from([
         MultipleEnds::new_as_all_zero(vec![(3, 3), (4, 4)]),
         MultipleEnds::new_as_all_zero(vec![(0, 0), (7, 7)]),
@@ -182,7 +169,7 @@ fn test4() {
         MultipleEnds::new_as_all_zero(vec![(0, 0), (7, 7)]),
         MultipleEnds::new_as_all_zero(vec![(0, 7)]),
         MultipleEnds::new_as_all_zero(vec![(7, 0)]),
-    ]));
+    ])).unwrap();
     let i3 = s.add((), (0, 7), VecDeque::from([
         MultipleEnds::new_as_all_zero(vec![(3, 3), (4, 4)]),
         MultipleEnds::new_as_all_zero(vec![(0, 0), (7, 7)]),
@@ -192,17 +179,14 @@ fn test4() {
         MultipleEnds::new_as_all_zero(vec![(0, 0), (7, 7)]),
         MultipleEnds::new_as_all_zero(vec![(7, 0)]),
         MultipleEnds::new_as_all_zero(vec![(0, 7)]),
-    ]));
+    ])).unwrap();
     let idxs = vec![i0, i1, i2, i3];
 
     let mut output = vec![];
 
     output.push(output_data(&s, &idxs, 0));
 
-    for t in 1..=148 {
-        s.step();
-        output.push(output_data(&s, &idxs, t));
-    }
+    s.run_for(148, |s| output.push(output_data(s, &idxs, s.time()))).unwrap();
 
     output_file(&"test4.json".to_string(), &output);
 }
@@ -214,37 +198,31 @@ fn test5() {
     let i0 = s.add((), (0, 0), VecDeque::from([
         MultipleEnds::new_as_all_zero(vec![(7, 4)]),
         MultipleEnds::new_as_all_zero(vec![(0, 0)]),
-    ]));
+    ])).unwrap();
     let i1 = s.add((), (0, 4), VecDeque::from([
         MultipleEnds::new_as_all_zero(vec![(7, 0)]),
         MultipleEnds::new_as_all_zero(vec![(0, 4)]),
-    ]));
+    ])).unwrap();
     let i2 = s.add((), (7, 4), VecDeque::from([
         MultipleEnds::new_as_all_zero(vec![(0, 0)]),
         MultipleEnds::new_as_all_zero(vec![(7, 4)]),
-    ]));
+    ])).unwrap();
     let i3 = s.add((), (7, 0), VecDeque::from([
         MultipleEnds::new_as_all_zero(vec![(0, 4)]),
         MultipleEnds::new_as_all_zero(vec![(7, 0)]),
-    ]));
+    ])).unwrap();
     let idxs = vec![i0, i1, i2, i3];
 
     let mut output = vec![];
 
     output.push(output_data(&s, &idxs, 0));
 
-    for t in 1..=40 {
-        s.step();
-        output.push(output_data(&s, &idxs, t));
-    }
+    s.run_for(40, |s| output.push(output_data(s, &idxs, s.time()))).unwrap();
 
     s.agent_destination_mut(i3).unwrap()
         .push_front(MultipleEnds::new_as_all_zero(vec![(4, 2)]));
 
-    for t in 41..=58 {
-        s.step();
-        output.push(output_data(&s, &idxs, t));
-    }
+    s.run_for(18, |s| output.push(output_data(s, &idxs, s.time()))).unwrap();
 
     output_file(&"test5.json".to_string(), &output);
 }
@@ -253,52 +231,320 @@ fn test5() {
 fn movement_test() {
     let mut s = Simulator::new(0, TestMap::new(7, 4), 5);
 
-    let i0 = s.add((), (6, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(0, 3)])]));
+    let i0 = s.add((), (6, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(0, 3)])])).unwrap();
 
     fn assert_seat(m: &Movement<TestMap, u32, ()>, expected: Vec<((usize, usize), Option<u32>)>) {
-        let actual = m.seats().into_iter().copied().collect::<HashSet<_>>();
+        let actual = m.seats().iter().copied().collect::<HashSet<_>>();
         assert_eq!(HashSet::from_iter(expected.into_iter()), actual);
     }
 
     let a = s.agent(i0).unwrap();
     assert_eq!(*a.current(), (6, 0));
     assert_seat(&s.movement_of(i0, 2).unwrap(), vec![((6, 0), Some(2)), ((6, 1), Some(3)), ((5, 1), Some(4)), ((5, 2), None)]);
-    assert!(s.movement_of(i0, 1).is_none());
+    assert!(matches!(s.movement_of(i0, 1), Err(Error::NoMovement { .. })));
 
-    s.step();
+    s.step().unwrap();
     
     for _ in 0..4 {
         assert_eq!(*s.agent(i0).unwrap().current(), (6, 0));
         assert_seat(&s.movement_of(i0, 1).unwrap(), vec![((4, 1), Some(2)), ((4, 2), Some(3)), ((5, 2), Some(4)), ((5, 3), None)]);
-        assert!(s.movement_of(i0, 6).is_none());
+        assert!(matches!(s.movement_of(i0, 6), Err(Error::NoMovement { .. })));
 
-        s.step();
+        s.step().unwrap();
     }
 
     for _ in 0..4 {
         assert_eq!(*s.agent(i0).unwrap().current(), (4, 1));
         assert_seat(&s.movement_of(i0, 5).unwrap(), vec![((2, 2), Some(2)), ((2, 1), Some(3)), ((1, 1), Some(4)), ((1, 0), None)]);
-        assert!(s.movement_of(i0, 8).is_none());
+        assert!(matches!(s.movement_of(i0, 8), Err(Error::NoMovement { .. })));
 
-        s.step();
+        s.step().unwrap();
     }
     
     for _ in 0..4 {
         assert_eq!(*s.agent(i0).unwrap().current(), (2, 2));
         assert_seat(&s.movement_of(i0, 7).unwrap(), vec![((0, 3), Some(2)), ((1, 3), Some(3)), ((1, 2), Some(4)), ((2, 2), None)]);
-        assert!(s.movement_of(Idx::new(99999), 8).is_none());
+        assert!(matches!(s.movement_of(Idx::new(99999), 8), Err(Error::UnknownAgent(_))));
 
-        s.step();
+        s.step().unwrap();
     }
     assert_eq!(*s.agent(i0).unwrap().current(), (0, 3));
     assert_seat(&s.movement_of(i0, 6).unwrap(), vec![((0, 3), Some(2)), ((0, 2), Some(3)), ((1, 2), Some(4)), ((1, 1), None)]);
 }
 
 
+#[test]
+fn event_clock_test() {
+    let scenario = |clock| {
+        let mut s = Simulator::new_with_clock(0, TestMap::new(8, 5), 5, clock);
+        s.add((), (0, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(7, 4)]), MultipleEnds::new_as_all_zero(vec![(0, 0)])])).unwrap();
+        s.add((), (0, 4), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(7, 0)]), MultipleEnds::new_as_all_zero(vec![(0, 4)])])).unwrap();
+        s.add((), (7, 4), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(0, 0)]), MultipleEnds::new_as_all_zero(vec![(7, 4)])])).unwrap();
+        s
+    };
+    let (mut s_tick, mut s_event) = (scenario(Clock::Tick), scenario(Clock::Event));
+
+    let mut n_event_steps = 0;
+    while s_event.time() < 60 {
+        while s_tick.time() < s_event.time() {
+            s_tick.step().unwrap();
+        }
+        assert_eq!(s_tick.time(), s_event.time());
+        for ((i, a), (j, b)) in s_tick.agents().iter().zip(s_event.agents().iter()) {
+            assert_eq!(i, j);
+            assert_eq!(a.current(), b.current());
+            assert_eq!(a.state(), b.state());
+        }
+        s_event.step().unwrap();
+        n_event_steps += 1;
+    }
+    assert!(n_event_steps < 60);
+    assert!(s_event.agents().values().all(|a| a.all_destinations().is_empty()));
+}
+
+#[test]
+fn step_to_next_event_test() {
+    let scenario = || {
+        let mut s = Simulator::new(0, TestMap::new(12, 10), 10);
+        let ps = [(11, 0), (11, 9), (0, 9)];
+        let idxs = (0..3)
+            .map(|i| s.add((), (0, 0), VecDeque::from_iter(
+                (0..3).map(|k| MultipleEnds::new_as_all_zero(vec![ps[(i + k) % 3]]))
+            )).unwrap())
+            .collect::<Vec<_>>();
+        (s, idxs)
+    };
+    let ((mut s0, idxs), (mut s1, _)) = (scenario(), scenario());
+
+    let assert_same = |s0: &Simulator<TestMap, u32>, s1: &Simulator<TestMap, u32>| {
+        assert_eq!(s0.time(), s1.time());
+        assert_eq!(output_data(s0, &idxs, 0).map, output_data(s1, &idxs, 0).map);
+        for ((i, a), (j, b)) in s0.agents().iter().zip(s1.agents().iter()) {
+            assert_eq!(i, j);
+            assert_eq!(a.current(), b.current());
+            assert_eq!(a.state(), b.state());
+            assert_eq!(a.all_destinations().len(), b.all_destinations().len());
+        }
+    };
+
+    let mut n_steps = 0;
+    while s1.time() < 60 {
+        s1.step_to_next_event().unwrap();
+        n_steps += 1;
+        while s0.time() < s1.time() {
+            s0.step().unwrap();
+        }
+        assert_same(&s0, &s1);
+    }
+    assert!(n_steps < 60);
+
+    for s in [&mut s0, &mut s1] {
+        s.remove(idxs[0]).unwrap();
+        s.agent_destination_mut(idxs[1]).unwrap().push_front(MultipleEnds::new_as_all_zero(vec![(5, 5)]));
+    }
+    s1.run_until(150).unwrap();
+    while s0.time() < 150 {
+        s0.step().unwrap();
+    }
+    assert_same(&s0, &s1);
+    assert!(s1.agent(idxs[0]).is_none());
+}
+
+fn snapshot_test_data() -> (Simulator<TestMap, u32>, Vec<Idx<(), u32>>) {
+    let mut s = Simulator::new(0, TestMap::new(8, 8), 5);
+    let idxs = vec![
+        s.add((), (0, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(7, 7)]), MultipleEnds::new_as_all_zero(vec![(0, 0)])])).unwrap(),
+        s.add((), (7, 7), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(0, 0)]), MultipleEnds::new_as_all_zero(vec![(7, 7)])])).unwrap(),
+        s.add((), (7, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(0, 7)]), MultipleEnds::new_as_all_zero(vec![(7, 0)])])).unwrap(),
+    ];
+    (s, idxs)
+}
+
+#[test]
+fn snapshot_test() {
+    let (mut s, idxs) = snapshot_test_data();
+
+    s.run_for(20, |_| {}).unwrap();
+    let snapshot = s.snapshot();
+    assert_eq!(snapshot.time(), 20);
+
+    let mut output0 = vec![];
+    s.run_for(30, |s| output0.push(output_data(s, &idxs, s.time()))).unwrap();
+
+    s.restore(snapshot.clone());
+    assert_eq!(s.time(), 20);
+
+    let mut output1 = vec![];
+    s.run_for(30, |s| output1.push(output_data(s, &idxs, s.time()))).unwrap();
+
+    assert_eq!(serde_json::to_value(&output0).unwrap(), serde_json::to_value(&output1).unwrap());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_simulator_test() {
+    let (mut s, idxs) = snapshot_test_data();
+    s.run_for(20, |_| {}).unwrap();
+
+    let json = serde_json::to_string(&s).unwrap();
+    let mut t: Simulator<TestMap, u32> = serde_json::from_str(&json).unwrap();
+
+    let snapshot = serde_json::to_string(&s.snapshot()).unwrap();
+    s.restore(serde_json::from_str(&snapshot).unwrap());
+
+    let (mut output0, mut output1) = (vec![], vec![]);
+    s.run_for(30, |s| output0.push(output_data(s, &idxs, s.time()))).unwrap();
+    t.run_for(30, |s| output1.push(output_data(s, &idxs, s.time()))).unwrap();
+
+    assert_eq!(serde_json::to_value(&output0).unwrap(), serde_json::to_value(&output1).unwrap());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_movement_test() {
+    let mut s = Simulator::new(0, TestMap::new(7, 4), 5);
+    let i0 = s.add((), (6, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(0, 3)])])).unwrap();
+
+    let m = s.movement_of(i0, 2).unwrap();
+    let json = serde_json::to_string(&m).unwrap();
+    let m2 = serde_json::from_str::<Movement<TestMap, u32, ()>>(&json).unwrap();
+    assert_eq!(m2.node(), m.node());
+    assert_eq!(m2.seats(), m.seats());
+}
+
+#[cfg(feature = "replay")]
+#[test]
+fn replay_test() {
+    use discrete_multi_nav::{agent_data::RemovalMode, index::allocator::FreeListAllocator, replay::{Input, Recorder, Recording, ReplayError, Replayer}};
+
+    let mut r = Recorder::new(0, TestMap::new(12, 10), 10, Clock::Tick);
+    let ps = [(11, 0), (11, 9), (0, 9)];
+    let idxs = (0..3)
+        .map(|i| r.add((), (0, 0), VecDeque::from_iter(
+            (0..3).map(|k| MultipleEnds::new_as_all_zero(vec![ps[(i + k) % 3]]))
+        )).unwrap())
+        .collect::<Vec<_>>();
+
+    for t in 0..80 {
+        match t {
+            20 => { r.edit_destinations(idxs[1], |d| d.push_front(MultipleEnds::new_as_all_zero(vec![(5, 5)]))); },
+            30 => { r.remove_with(idxs[0], RemovalMode::AtNextNode).unwrap(); },
+            40 => { r.add((), (0, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(11, 9)])])).unwrap(); },
+            50 => {
+                r.remove_with(idxs[2], RemovalMode::Immediate).unwrap();
+                r.cancel_removal(idxs[2]).unwrap();
+            },
+            60 => { r.remove_with(idxs[1], RemovalMode::Immediate).unwrap(); },
+            62 => {
+                r.set_planner(Planner::Dijkstra);
+                r.set_allocator(FreeListAllocator::default());
+                r.add_with_idx(Idx::new(10), (), (0, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(11, 0)])])).unwrap();
+                r.add((), (0, 9), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(11, 9)])])).unwrap();
+            },
+            66 => { r.cancel_path(Idx::new(10)).unwrap(); },
+            70 => {
+                r.edit_destinations(Idx::new(10), |d| d[0] = MultipleEnds::new_as_all_zero(vec![(6, 9)]));
+                r.replan(Idx::new(10)).unwrap();
+            },
+            _ => {},
+        }
+        r.step().unwrap();
+    }
+    let (s, recording) = r.finish();
+
+    let mut buf = vec![];
+    recording.write(&mut buf).unwrap();
+    let recording: Recording<(usize, usize), u32, (), u32> = Recording::read(buf.as_slice()).unwrap();
+    assert_eq!(recording.n_steps(), 80);
+
+    let mut replayer = Replayer::new_with_assertion(recording.clone(), TestMap::new(12, 10));
+    replayer.run().unwrap();
+    assert!(replayer.is_finished());
+    for ((i, a), (j, b)) in s.agents().iter().zip(replayer.simulator().agents().iter()) {
+        assert_eq!(i, j);
+        assert_eq!(a.current(), b.current());
+        assert_eq!(a.state(), b.state());
+    }
+
+    let mut replayer = Replayer::new_with_assertion(recording, TestMap::new(12, 11));
+    assert!(matches!(replayer.run(), Err(ReplayError::StateMismatch { .. })));
+
+    // removals recorded without the mode are graceful
+    let input = serde_json::from_str::<Input<(usize, usize), u32, (), u32>>(r#"{"Remove":{"idx":3}}"#).unwrap();
+    assert!(matches!(input, Input::Remove { idx: 3, mode: RemovalMode::Graceful }));
+}
+
+#[test]
+fn float_cost_test() {
+    let mut s = Simulator::new_with_clock(FloatCost::new(0.), FloatMap::new(6, 6), FloatCost::new(3.), Clock::Event);
+
+    let i0 = s.add((), (0, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(5, 5)])])).unwrap();
+    let i1 = s.add((), (5, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(0, 5)])])).unwrap();
+
+    for _ in 0..100 {
+        s.step().unwrap();
+
+        let seats = (0..s.map().nx())
+            .flat_map(|x| (0..s.map().ny()).map(move |y| (x, y)))
+            .filter_map(|p| s.map()[p].get())
+            .collect::<Vec<_>>();
+        for i in [i0, i1] {
+            let a = s.agent(i).unwrap();
+            assert_eq!(s.map()[*a.current()].get(), Some(i));
+            assert!(seats.contains(&i));
+        }
+
+        if s.agents().values().all(|a| a.all_destinations().is_empty()) {
+            break;
+        }
+    }
+    assert_eq!(*s.agent(i0).unwrap().current(), (5, 5));
+    assert_eq!(*s.agent(i1).unwrap().current(), (0, 5));
+    assert!(s.time().value().fract() != 0.);
+}
+
+#[test]
+fn short_move_hold_test() {
+    // a move cheaper than a tick still takes a step with Clock::Tick
+    for (clock, dt) in [(Clock::Tick, 1.), (Clock::Event, 0.5)] {
+        let mut s = Simulator::new_with_clock(FloatCost::new(0.), FloatMap::with_scale(4, 1, 0.5), FloatCost::new(5.), clock);
+        let i = s.add((), (0, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(3, 0)])])).unwrap();
+        s.step().unwrap();
+
+        let mut reserved = s.reserved_seats(i).unwrap().to_vec();
+        reserved.sort_by_key(|(seat, _)| *seat);
+        let t = |k: f64| Some(FloatCost::new(k * dt));
+        assert_eq!(reserved, vec![((0, 0), t(1.)), ((1, 0), t(2.)), ((2, 0), t(3.)), ((3, 0), None)]);
+
+        while !s.agents().values().all(|a| a.all_destinations().is_empty()) {
+            s.step().unwrap();
+            assert_eq!(s.map()[*s.agent(i).unwrap().current()].get(), Some(i));
+        }
+        assert_eq!(*s.agent(i).unwrap().current(), (3, 0));
+    }
+}
+
+#[test]
+fn planner_test() {
+    let mut times = vec![];
+    for planner in [Planner::AStar, Planner::Dijkstra] {
+        let mut s = Simulator::new(0, TestMap::new(8, 5), 5);
+        s.set_planner(planner);
+        let i0 = s.add((), (0, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(7, 4)])])).unwrap();
+
+        assert_eq!(s.planner(), planner);
+        assert_eq!(s.run_until_idle(100).unwrap(), StopReason::Completed);
+        assert_eq!(*s.agent(i0).unwrap().current(), (7, 4));
+        times.push(s.time());
+    }
+    // both find shortest paths
+    assert_eq!(times[0], times[1]);
+}
+
 fn performance_test_data(map_size: usize, n_agents: usize, n_destinations: usize) -> (Simulator<TestMap, u32>, Vec<Idx<(), u32>>) {
     let mut s = Simulator::new(0, TestMap::new(map_size, map_size), 5);
 
-    let mut rng = thread_rng();
+    let mut rng = StdRng::seed_from_u64((map_size * n_agents * n_destinations) as u64);
     
     let idxs = (0..n_agents)
         .map(|_| {
@@ -308,7 +554,7 @@ fn performance_test_data(map_size: usize, n_agents: usize, n_destinations: usize
                     let (x, y) = (rng.gen_range(0..map_size), rng.gen_range(0..map_size));
                     MultipleEnds::new_as_all_zero(vec![(x, y)])
                 });
-            s.add((), (x, y), VecDeque::from_iter(dests))
+            s.add((), (x, y), VecDeque::from_iter(dests)).unwrap()
         })
         .collect::<Vec<_>>();
 
@@ -324,31 +570,51 @@ fn performance_test_visual() {
     let mut output = vec![];
     output.push(output_data(&s, &idxs, 0));
 
-    let mut n_stops = idxs.iter().map(|&i| (i, 0)).collect::<HashMap<_, _>>();
+    let reason = s.run_until_idle_with(n_step, |s| output.push(output_data(s, &idxs, s.time()))).unwrap();
+    report(reason, s.time());
 
-    for t in 1..=n_step {
-        s.step();
-        output.push(output_data(&s, &idxs, t));
+    output_file(&"visual_test.json".to_string(), &output);
+}
 
-        for (idx, a) in s.agents() {
-            let n = n_stops.get_mut(idx).unwrap();
-            match a.state() {
-                AgentState::Moving { nexts: _ } => { *n = 0 },
-                _ => { *n += 1 }
-            }
-        }
-        if n_stops.values().all(|&n| n >= 2) {
-            println!("finished at t = {}", t);
-            if s.agents().values().all(|a| a.all_destinations().is_empty()) {
-                println!("all agents completed their movements.");
-            } else {
-                println!("some agents may finished with deadlock.");
-            }
-            break;
-        }
+fn report(reason: StopReason, t: u32) {
+    match reason {
+        StopReason::Completed => println!("all agents completed their movements at t = {}.", t),
+        StopReason::Deadlock => println!("some agents finished with deadlock at t = {}.", t),
+        StopReason::StepLimit => println!("not finished until t = {}.", t),
     }
+}
 
-    output_file(&"visual_test.json".to_string(), &output);
+#[test]
+fn validation_test() {
+    let (mut s, _) = performance_test_data(20, 10, 3);
+    s.set_validation(true);
+    s.run_until_idle(300).unwrap();
+    assert_eq!(s.validate(), Ok(()));
+}
+
+#[test]
+fn run_until_idle_test() {
+    let mut s = Simulator::new(0, TestMap::new(8, 5), 5);
+    s.add((), (0, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(7, 4)])])).unwrap();
+    s.add((), (0, 4), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(7, 0)])])).unwrap();
+
+    assert_eq!(s.run_until_idle(3).unwrap(), StopReason::StepLimit);
+    assert_eq!(s.time(), 3);
+    assert_eq!(s.run_until_idle(100).unwrap(), StopReason::Completed);
+    assert!(s.time() < 100);
+    assert!(s.is_completed());
+
+    // two agents which want to swap their places
+    let mut s = Simulator::new(0, TestMap::new(3, 2), 5);
+    s.add((), (0, 0), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(2, 1)])])).unwrap();
+    s.add((), (2, 1), VecDeque::from([MultipleEnds::new_as_all_zero(vec![(0, 0)])])).unwrap();
+
+    assert_eq!(s.run_until_idle(100).unwrap(), StopReason::Deadlock);
+    assert!(s.time() < 100);
+
+    let mut n = 0;
+    s.run_for(5, |_| n += 1).unwrap();
+    assert_eq!(n, 5);
 }
 
 #[test]
@@ -370,41 +636,14 @@ fn performance_test() {
         for n_agent in n_agents {
 
             for _ in 0..n_try {
-                let (mut s, idxs) = performance_test_data(map_size, n_agent, 3);
-
-                let mut n_stops = idxs.iter().map(|&i| (i, 0)).collect::<HashMap<_, _>>();
-
-                let mut total_time = 0;
-                let mut n = 0;
-                for t in 1..=n_step {
-
-                    let t0 = Instant::now();
-                    s.step();
-                    total_time += t0.elapsed().as_micros();
-            
-                    for (idx, a) in s.agents() {
-                        let n = n_stops.get_mut(idx).unwrap();
-                        match a.state() {
-                            AgentState::Moving { nexts: _ } => { *n = 0 },
-                            _ => { *n += 1 }
-                        }
-                    }
-                    if n_stops.values().all(|&n| n >= 2) {
-                        println!("finished at t = {}", t);
-                        if s.agents().values().all(|a| a.all_destinations().is_empty()) {
-                            println!("all agents completed their movements.");
-                        } else {
-                            println!("some agents may finished with deadlock.");
-                        }
-                        n = t;
-                        break;
-                    }
-                }
-                if n == 0 {
-                    n = n_step;
-                }
-
-                total_time /= 1000; // milli
+                let (mut s, _) = performance_test_data(map_size, n_agent, 3);
+
+                let t0 = Instant::now();
+                let reason = s.run_until_idle(n_step).unwrap();
+                let total_time = t0.elapsed().as_micros() / 1000; // milli
+
+                report(reason, s.time());
+                let n = s.time();
 
                 f.write_all(format!("{},{},{},{},{}\n", map_size, n_agent, total_time, n, total_time as f64 / n as f64).as_bytes()).unwrap();
             }
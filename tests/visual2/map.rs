@@ -1,7 +1,9 @@
 use std::{ops::{Index, IndexMut}, vec::IntoIter};
 
-use discrete_multi_nav::{index::index::Idx, map::{Heuristic, Map}, pathfind::common::MultipleEnds, seat::Seat};
+use discrete_multi_nav::{index::index::Idx, map::{Heuristic, Map}, pathfind::common::MultipleEnds, seat::{Seat, SeatError}};
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) struct TestMap {
     occupied: Vec<Vec<TestNode>>,
 }
@@ -116,6 +118,7 @@ const BS: [[(i32, i32, u32); 3]; 8] = [
 ];
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) struct TestNode(Option<Idx<(), u32>>);
 
 impl TestNode {
@@ -145,11 +148,19 @@ impl Seat<(), u32> for TestNode {
         }
     }
 
-    fn add(&mut self, idx: Idx<(), u32>) {
-        self.0 = Some(idx)
+    fn add(&mut self, idx: Idx<(), u32>) -> Result<(), SeatError> {
+        if !self.is_empty_for(idx) {
+            return Err(SeatError::AlreadyOccupied)
+        }
+        self.0 = Some(idx);
+        Ok(())
     }
 
-    fn remove(&mut self, _: Idx<(), u32>) {
-        self.0 = None
+    fn remove(&mut self, idx: Idx<(), u32>) -> Result<(), SeatError> {
+        if self.0 != Some(idx) {
+            return Err(SeatError::NotHeld)
+        }
+        self.0 = None;
+        Ok(())
     }
 }
\ No newline at end of file